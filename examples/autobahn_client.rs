@@ -2,7 +2,7 @@ use std::str::FromStr;
 
 use futures_util::{SinkExt, StreamExt};
 use http::Uri;
-use tokio_websockets::{ClientBuilder, Connector, Error, Limits};
+use tokio_websockets::{extensions::PerMessageDeflate, ClientBuilder, Connector, Error, Limits};
 
 #[cfg(feature = "simd")]
 macro_rules! agent {
@@ -56,6 +56,7 @@ async fn run_test(case: u32) -> Result<(), Error> {
 
     let (mut stream, _) = ClientBuilder::from_uri(uri)
         .limits(Limits::unlimited())
+        .permessage_deflate(PerMessageDeflate::new())
         .connector(&Connector::Plain)
         .connect()
         .await?;
@@ -2,7 +2,7 @@ use std::net::SocketAddr;
 
 use futures_util::{SinkExt, StreamExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio_websockets::{Error, Limits, ServerBuilder};
+use tokio_websockets::{extensions::PerMessageDeflate, Error, Limits, ServerBuilder};
 
 #[cfg(feature = "simd")]
 const PORT: u16 = 9004;
@@ -21,6 +21,7 @@ async fn accept_connection(stream: TcpStream) {
 async fn handle_connection(stream: TcpStream) -> Result<(), Error> {
     let (_request, mut ws_stream) = ServerBuilder::new()
         .limits(Limits::unlimited())
+        .permessage_deflate(PerMessageDeflate::new())
         .accept(stream)
         .await?;
 
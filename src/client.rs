@@ -21,7 +21,10 @@ use tokio::{
 };
 use tokio_util::codec::FramedRead;
 
+#[cfg(feature = "permessage-deflate")]
+use crate::extensions::PerMessageDeflate;
 use crate::{
+    extensions::{self, Extension, ExtensionConfig},
     proto::{Config, Limits, Role},
     resolver::{self, Resolver},
     upgrade::{self, server_response},
@@ -59,9 +62,16 @@ fn default_port(uri: &Uri) -> Option<u16> {
     }
 }
 
-/// Builds a HTTP/1.1 Upgrade request for a URI with extra headers and a
-/// WebSocket key.
-fn build_request(uri: &Uri, key: &[u8], headers: &HeaderMap) -> Vec<u8> {
+/// Builds a HTTP/1.1 Upgrade request for a URI with extra headers, a WebSocket
+/// key, an optional `Sec-WebSocket-Extensions` value and the subprotocols to
+/// offer.
+fn build_request(
+    uri: &Uri,
+    key: &[u8],
+    headers: &HeaderMap,
+    extensions: Option<&str>,
+    subprotocols: &[String],
+) -> Vec<u8> {
     let mut buf = Vec::new();
 
     buf.extend_from_slice(b"GET ");
@@ -90,6 +100,18 @@ fn build_request(uri: &Uri, key: &[u8], headers: &HeaderMap) -> Vec<u8> {
     buf.extend_from_slice(key);
     buf.extend_from_slice(b"\r\nSec-WebSocket-Version: 13\r\n");
 
+    if let Some(extensions) = extensions {
+        buf.extend_from_slice(b"Sec-WebSocket-Extensions: ");
+        buf.extend_from_slice(extensions.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    if !subprotocols.is_empty() {
+        buf.extend_from_slice(b"Sec-WebSocket-Protocol: ");
+        buf.extend_from_slice(subprotocols.join(", ").as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+
     for (name, value) in headers {
         buf.extend_from_slice(name.as_str().as_bytes());
         buf.extend_from_slice(b": ");
@@ -118,6 +140,10 @@ pub struct Builder<'a, R: Resolver = resolver::Gai> {
     limits: Limits,
     /// Headers to be sent with the upgrade request.
     headers: HeaderMap,
+    /// Extensions to offer to the server.
+    extensions: ExtensionConfig,
+    /// Subprotocols to offer to the server.
+    subprotocols: Vec<String>,
 }
 
 impl Builder<'_> {
@@ -132,6 +158,8 @@ impl Builder<'_> {
             config: Config::default(),
             limits: Limits::default(),
             headers: HeaderMap::new(),
+            extensions: ExtensionConfig::default(),
+            subprotocols: Vec::new(),
         }
     }
 
@@ -148,6 +176,8 @@ impl Builder<'_> {
             config: Config::default(),
             limits: Limits::default(),
             headers: HeaderMap::new(),
+            extensions: ExtensionConfig::default(),
+            subprotocols: Vec::new(),
         }
     }
 }
@@ -161,12 +191,16 @@ impl<'a, R: Resolver> Builder<'a, R> {
     /// - `connection`
     /// - `sec-websocket-key`
     /// - `sec_websocket_version`
+    /// - `sec-websocket-extensions`
+    /// - `sec-websocket-protocol`
     pub const DISALLOWED_HEADERS: &'static [HeaderName] = &[
         header::HOST,
         header::UPGRADE,
         header::CONNECTION,
         header::SEC_WEBSOCKET_KEY,
         header::SEC_WEBSOCKET_VERSION,
+        header::SEC_WEBSOCKET_EXTENSIONS,
+        header::SEC_WEBSOCKET_PROTOCOL,
     ];
 
     /// Sets the [`Uri`] to connect to. This URI must use the `ws` or `wss`
@@ -208,6 +242,8 @@ impl<'a, R: Resolver> Builder<'a, R> {
             config,
             limits,
             headers,
+            extensions,
+            subprotocols,
         } = self;
 
         Builder {
@@ -217,6 +253,8 @@ impl<'a, R: Resolver> Builder<'a, R> {
             config,
             limits,
             headers,
+            extensions,
+            subprotocols,
         }
     }
 
@@ -236,6 +274,52 @@ impl<'a, R: Resolver> Builder<'a, R> {
         self
     }
 
+    /// Adds an extension to offer to the server. Extensions are offered in the
+    /// order they were added in.
+    #[must_use]
+    pub fn extension<E: Extension + 'static>(mut self, extension: E) -> Self {
+        self.extensions.push(Box::new(extension));
+
+        self
+    }
+
+    /// Offers the permessage-deflate extension with the given configuration to
+    /// the server. This is a shorthand for [`Builder::extension`].
+    #[cfg(feature = "permessage-deflate")]
+    #[must_use]
+    pub fn permessage_deflate(self, config: PerMessageDeflate) -> Self {
+        self.extension(config)
+    }
+
+    /// Sets the subprotocols to offer to the server in the
+    /// `Sec-WebSocket-Protocol` header, in order of preference. The handshake
+    /// fails with [`upgrade::Error::InvalidSubprotocol`] if the server selects
+    /// a subprotocol that was not offered.
+    ///
+    /// The selected subprotocol is available via
+    /// [`WebSocketStream::subprotocol`].
+    ///
+    /// # Panics
+    ///
+    /// If any of the subprotocols is empty or contains characters that are not
+    /// allowed in HTTP tokens.
+    #[must_use]
+    pub fn subprotocols<I>(mut self, subprotocols: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.subprotocols = subprotocols.into_iter().map(Into::into).collect();
+        assert!(
+            self.subprotocols
+                .iter()
+                .all(|name| extensions::is_token(name)),
+            "subprotocols must be valid HTTP tokens"
+        );
+
+        self
+    }
+
     /// Adds an extra HTTP header to the handshake request.
     ///
     /// # Errors
@@ -322,7 +406,13 @@ impl<'a, R: Resolver> Builder<'a, R> {
         let key_base64 = make_key();
 
         let upgrade_codec = server_response::Codec::new(&key_base64);
-        let request = build_request(uri, &key_base64, &self.headers);
+        let request = build_request(
+            uri,
+            &key_base64,
+            &self.headers,
+            self.extensions.offer().as_deref(),
+            &self.subprotocols,
+        );
         stream.write_all(&request).await?;
 
         let mut framed = FramedRead::new(stream, upgrade_codec);
@@ -330,12 +420,45 @@ impl<'a, R: Resolver> Builder<'a, R> {
             .await
             .ok_or(Error::Io(io::ErrorKind::UnexpectedEof.into()))??;
 
+        let extensions = self.extensions.confirm(
+            res.headers()
+                .get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+                .iter()
+                .map(HeaderValue::as_bytes),
+        )?;
+
+        let subprotocol = self.confirm_subprotocol(&res)?;
+
         Ok((
-            WebSocketStream::from_framed(framed, Role::Client, self.config, self.limits),
+            WebSocketStream::from_framed(
+                framed,
+                Role::Client,
+                self.config,
+                self.limits,
+                extensions,
+                subprotocol,
+            ),
             res,
         ))
     }
 
+    /// Returns the subprotocol selected by the server, after checking that it
+    /// was offered.
+    fn confirm_subprotocol(&self, res: &upgrade::Response) -> Result<Option<String>, Error> {
+        let mut selected = upgrade::parse_subprotocols(
+            res.headers().get_all(header::SEC_WEBSOCKET_PROTOCOL).iter(),
+        );
+        let subprotocol = selected.next();
+
+        if selected.next().is_some()
+            || subprotocol.is_some_and(|name| !self.subprotocols.iter().any(|s| s == name))
+        {
+            return Err(Error::Upgrade(upgrade::Error::InvalidSubprotocol));
+        }
+
+        Ok(subprotocol.map(str::to_owned))
+    }
+
     /// Takes over an already established stream that has already performed a
     /// HTTP upgrade handshake and uses it to send and receive WebSocket
     /// messages.
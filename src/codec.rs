@@ -0,0 +1,360 @@
+//! Typed messages on top of a [`WebSocketStream`].
+//!
+//! A [`Typed`] adapter turns a stream of [`Message`]s into a stream of values
+//! of a type `T`, which are encoded into and decoded from messages by a
+//! [`MessageCodec`]. Codecs for common formats are available behind feature
+//! flags:
+//!
+//! - [`Json`] with the `serde_json` feature, sent as text messages
+//! - [`Cbor`] with the `ciborium` feature, sent as binary messages
+//! - [`MessagePack`] with the `rmp-serde` feature, sent as binary messages
+//! - [`Protobuf`] with the `prost` feature, sent as binary messages
+//!
+//! [`WebSocketStream`]: crate::WebSocketStream
+use std::{
+    fmt,
+    marker::PhantomData,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+
+use crate::{CloseCode, CloseFrame, Message};
+
+/// A format that values of type `T` are encoded into and decoded from
+/// [`Message`]s with.
+pub trait MessageCodec<T> {
+    /// Error returned if a value cannot be encoded.
+    type EncodeError;
+    /// Error returned if a message cannot be decoded.
+    type DecodeError;
+
+    /// Encodes a value into a message.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the value cannot be encoded.
+    fn encode(&mut self, item: &T) -> Result<Message, Self::EncodeError>;
+
+    /// Decodes a received text or binary message into a value.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the message cannot be decoded.
+    fn decode(&mut self, message: Message) -> Result<T, Self::DecodeError>;
+}
+
+/// Error returned by a [`Typed`] adapter.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Reading from or writing to the underlying stream failed.
+    WebSocket(crate::Error),
+    /// Encoding or decoding a message failed.
+    Codec(E),
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WebSocket(e) => e.fmt(f),
+            Self::Codec(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::WebSocket(e) => Some(e),
+            Self::Codec(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<crate::Error> for Error<E> {
+    fn from(err: crate::Error) -> Self {
+        Self::WebSocket(err)
+    }
+}
+
+/// An adapter for a stream of [`Message`]s, such as a [`WebSocketStream`],
+/// that reads and writes values of type `T` encoded with the [`MessageCodec`]
+/// `C`.
+///
+/// The adapter implements [`futures_sink::Sink`] and [`futures_core::Stream`].
+/// Ping, pong and close messages are not passed to the codec and skipped when
+/// reading, the underlying stream still handles them.
+///
+/// If a received message cannot be decoded, the connection is closed with
+/// [`CloseCode::INVALID_FRAME_PAYLOAD_DATA`] by default and the error is
+/// returned. The close code can be changed via
+/// [`Typed::close_on_decode_error`]. The close frame is sent along with the
+/// next write or flush of the stream, which happens at the latest when reading
+/// from it again.
+///
+/// [`WebSocketStream`]: crate::WebSocketStream
+#[derive(Debug)]
+pub struct Typed<S, T, C> {
+    /// The underlying stream of messages.
+    inner: S,
+    /// The codec that values are encoded and decoded with.
+    codec: C,
+    /// Close code sent to the peer if a message cannot be decoded.
+    decode_error_code: Option<CloseCode>,
+    /// Close message that is to be sent because a message could not be
+    /// decoded.
+    pending_close: Option<Message>,
+    /// Marker for the type of values.
+    _item: PhantomData<fn(T) -> T>,
+}
+
+impl<S, T, C> Typed<S, T, C> {
+    /// Creates an adapter that reads and writes values via `inner` encoded
+    /// with `codec`.
+    pub fn new(inner: S, codec: C) -> Self {
+        Self {
+            inner,
+            codec,
+            decode_error_code: Some(CloseCode::INVALID_FRAME_PAYLOAD_DATA),
+            pending_close: None,
+            _item: PhantomData,
+        }
+    }
+
+    /// Sets the close code that the connection is closed with if a received
+    /// message cannot be decoded, such as
+    /// [`CloseCode::INVALID_FRAME_PAYLOAD_DATA`] or
+    /// [`CloseCode::UNSUPPORTED_DATA`]. `None` or a code that may not be sent
+    /// keeps the connection open and only returns the error. The default is
+    /// [`CloseCode::INVALID_FRAME_PAYLOAD_DATA`].
+    #[must_use]
+    pub fn close_on_decode_error(mut self, code: Option<CloseCode>) -> Self {
+        self.decode_error_code = code;
+
+        self
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the codec.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns a mutable reference to the codec.
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Returns the underlying stream, dropping the codec.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, T, C> Typed<S, T, C>
+where
+    S: Sink<Message, Error = crate::Error> + Unpin,
+{
+    /// Queues the close frame that is pending because a message could not be
+    /// decoded, if any.
+    fn poll_queue_close(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), crate::Error>> {
+        if self.pending_close.is_some() {
+            ready!(Pin::new(&mut self.inner).poll_ready(cx))?;
+        }
+
+        if let Some(close) = self.pending_close.take() {
+            Pin::new(&mut self.inner).start_send(close)?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S, T, C> Stream for Typed<S, T, C>
+where
+    S: Stream<Item = Result<Message, crate::Error>> + Sink<Message, Error = crate::Error> + Unpin,
+    C: MessageCodec<T> + Unpin,
+{
+    type Item = Result<T, Error<C::DecodeError>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_queue_close(cx))?;
+
+        loop {
+            let Some(message) = ready!(Pin::new(&mut this.inner).poll_next(cx)?) else {
+                return Poll::Ready(None);
+            };
+
+            if !message.is_text() && !message.is_binary() {
+                continue;
+            }
+
+            return match this.codec.decode(message) {
+                Ok(item) => Poll::Ready(Some(Ok(item))),
+                Err(e) => {
+                    // Only one close frame is sent
+                    if let Some(code) = this.decode_error_code.take() {
+                        if let Ok(frame) = CloseFrame::new(code, "decode error") {
+                            this.pending_close = Some(frame.into());
+                        }
+
+                        // Queue the close frame right away if possible, errors surface on the next
+                        // call and must not hide the decode error
+                        _ = this.poll_queue_close(cx);
+                    }
+
+                    Poll::Ready(Some(Err(Error::Codec(e))))
+                }
+            };
+        }
+    }
+}
+
+impl<S, T, C> Sink<T> for Typed<S, T, C>
+where
+    S: Sink<Message, Error = crate::Error> + Unpin,
+    C: MessageCodec<T> + Unpin,
+{
+    type Error = Error<C::EncodeError>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        // A pending close frame goes first
+        ready!(this.poll_queue_close(cx))?;
+
+        Poll::Ready(Ok(ready!(Pin::new(&mut this.inner).poll_ready(cx))?))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let message = this.codec.encode(&item).map_err(Error::Codec)?;
+
+        Ok(Pin::new(&mut this.inner).start_send(message)?)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_queue_close(cx))?;
+
+        Poll::Ready(Ok(ready!(Pin::new(&mut this.inner).poll_flush(cx))?))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_queue_close(cx))?;
+
+        Poll::Ready(Ok(ready!(Pin::new(&mut this.inner).poll_close(cx))?))
+    }
+}
+
+/// A [`MessageCodec`] for JSON via [`serde_json`]. Values are sent as text
+/// messages, both text and binary messages are decoded.
+#[cfg(feature = "serde_json")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Json;
+
+#[cfg(feature = "serde_json")]
+impl<T> MessageCodec<T> for Json
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type DecodeError = serde_json::Error;
+    type EncodeError = serde_json::Error;
+
+    fn encode(&mut self, item: &T) -> Result<Message, Self::EncodeError> {
+        Ok(Message::text(serde_json::to_string(item)?))
+    }
+
+    fn decode(&mut self, message: Message) -> Result<T, Self::DecodeError> {
+        serde_json::from_slice(message.as_payload())
+    }
+}
+
+/// A [`MessageCodec`] for CBOR via [`ciborium`]. Values are sent as binary
+/// messages, both text and binary messages are decoded.
+#[cfg(feature = "ciborium")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Cbor;
+
+#[cfg(feature = "ciborium")]
+impl<T> MessageCodec<T> for Cbor
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type DecodeError = ciborium::de::Error<std::io::Error>;
+    type EncodeError = ciborium::ser::Error<std::io::Error>;
+
+    fn encode(&mut self, item: &T) -> Result<Message, Self::EncodeError> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(item, &mut buf)?;
+
+        Ok(Message::binary(buf))
+    }
+
+    fn decode(&mut self, message: Message) -> Result<T, Self::DecodeError> {
+        ciborium::from_reader(&**message.as_payload())
+    }
+}
+
+/// A [`MessageCodec`] for `MessagePack` via [`rmp_serde`]. Structs are
+/// encoded as maps with field names. Values are sent as binary messages, both
+/// text and binary messages are decoded.
+#[cfg(feature = "rmp-serde")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MessagePack;
+
+#[cfg(feature = "rmp-serde")]
+impl<T> MessageCodec<T> for MessagePack
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type DecodeError = rmp_serde::decode::Error;
+    type EncodeError = rmp_serde::encode::Error;
+
+    fn encode(&mut self, item: &T) -> Result<Message, Self::EncodeError> {
+        Ok(Message::binary(rmp_serde::to_vec_named(item)?))
+    }
+
+    fn decode(&mut self, message: Message) -> Result<T, Self::DecodeError> {
+        rmp_serde::from_slice(message.as_payload())
+    }
+}
+
+/// A [`MessageCodec`] for Protocol Buffers via [`prost`]. Values are sent as
+/// binary messages, both text and binary messages are decoded.
+#[cfg(feature = "prost")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Protobuf;
+
+#[cfg(feature = "prost")]
+impl<T> MessageCodec<T> for Protobuf
+where
+    T: prost::Message + Default,
+{
+    type DecodeError = prost::DecodeError;
+    type EncodeError = std::convert::Infallible;
+
+    fn encode(&mut self, item: &T) -> Result<Message, Self::EncodeError> {
+        Ok(Message::binary(item.encode_to_vec()))
+    }
+
+    fn decode(&mut self, message: Message) -> Result<T, Self::DecodeError> {
+        T::decode(bytes::Bytes::from(message.into_payload()))
+    }
+}
@@ -31,6 +31,15 @@ pub enum Error {
     Protocol(ProtocolError),
     /// Payload length limit was exceeded.
     PayloadTooLong { len: usize, max_len: usize },
+    /// Frame payload length limit was exceeded.
+    FrameTooLong { len: usize, max_len: usize },
+    /// A message was fragmented into more frames than allowed.
+    TooManyFragments { max_fragments: usize },
+    /// The peer did not respond to an automatic ping in time.
+    KeepaliveTimeout,
+    /// The close handshake did not complete in time, so the connection was
+    /// torn down without the close being acknowledged.
+    CloseTimeout,
     /// I/O error.
     Io(io::Error),
     /// TLS error originating in [`native_tls`].
@@ -148,6 +157,19 @@ impl fmt::Display for Error {
                 f.write_str(" exceeds the limit of ")?;
                 max_len.fmt(f)
             }
+            Error::FrameTooLong { len, max_len } => {
+                f.write_str("frame payload length of ")?;
+                len.fmt(f)?;
+                f.write_str(" exceeds the limit of ")?;
+                max_len.fmt(f)
+            }
+            Error::TooManyFragments { max_fragments } => {
+                f.write_str("message exceeds the limit of ")?;
+                max_fragments.fmt(f)?;
+                f.write_str(" fragments")
+            }
+            Error::KeepaliveTimeout => f.write_str("peer did not respond to ping in time"),
+            Error::CloseTimeout => f.write_str("close handshake was not completed in time"),
             Error::Io(e) => e.fmt(f),
             #[cfg(feature = "native-tls")]
             Error::NativeTls(e) => e.fmt(f),
@@ -193,10 +215,27 @@ impl fmt::Display for Error {
     }
 }
 
+impl Error {
+    /// Converts the error into an [`io::Error`] for use in I/O trait
+    /// implementations, unwrapping I/O errors.
+    pub(crate) fn into_io_error(self) -> io::Error {
+        match self {
+            Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::AlreadyClosed | Error::CannotResolveHost | Error::PayloadTooLong { .. } => None,
+            Error::AlreadyClosed
+            | Error::CannotResolveHost
+            | Error::PayloadTooLong { .. }
+            | Error::FrameTooLong { .. }
+            | Error::TooManyFragments { .. }
+            | Error::KeepaliveTimeout
+            | Error::CloseTimeout => None,
             #[cfg(feature = "client")]
             Error::NoUriConfigured | Error::DisallowedHeader => None,
             #[cfg(all(
@@ -0,0 +1,541 @@
+//! Implementation of the permessage-deflate extension as specified in
+//! [RFC 7692](https://datatracker.ietf.org/doc/html/rfc7692).
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+use super::{Extension, NegotiatedExtension, Param};
+use crate::{
+    proto::{Frame, OpCode, ProtocolError, Rsv},
+    Error, Payload,
+};
+
+/// The name of the extension in the `Sec-WebSocket-Extensions` header.
+const NAME: &str = "permessage-deflate";
+
+/// The bytes that every flushed DEFLATE block ends with. They are removed from
+/// the end of every message and have to be appended again when decompressing.
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// The largest supported LZ77 sliding window size, as a base-2 logarithm.
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// The smallest LZ77 sliding window size we can compress with, as a base-2
+/// logarithm. While RFC 7692 allows 8, zlib does not support it.
+const MIN_WINDOW_BITS: u8 = 9;
+
+/// Configuration for the permessage-deflate extension specified in
+/// [RFC 7692](https://datatracker.ietf.org/doc/html/rfc7692).
+///
+/// When negotiated, the payloads of all text and binary messages will be
+/// compressed using DEFLATE. Compressed messages are subject to the same
+/// [`Limits`] as uncompressed messages, which are applied to the decompressed
+/// size of the message.
+///
+/// The extension is enabled by passing it to `ClientBuilder::extension` or
+/// `ServerBuilder::extension`.
+///
+/// [`Limits`]: crate::Limits
+#[derive(Debug, Clone, Copy)]
+pub struct PerMessageDeflate {
+    /// The DEFLATE compression level.
+    compression_level: u32,
+    /// Whether the server should reset its compression context after every
+    /// message.
+    server_no_context_takeover: bool,
+    /// Whether the client should reset its compression context after every
+    /// message.
+    client_no_context_takeover: bool,
+    /// The maximum sliding window size the server may compress with.
+    server_max_window_bits: u8,
+    /// The maximum sliding window size the client may compress with.
+    client_max_window_bits: u8,
+}
+
+impl PerMessageDeflate {
+    /// Creates a new permessage-deflate configuration with the default
+    /// compression level, context takeover and the maximum sliding window size
+    /// in both directions.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            compression_level: Compression::default().level(),
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            server_max_window_bits: MAX_WINDOW_BITS,
+            client_max_window_bits: MAX_WINDOW_BITS,
+        }
+    }
+
+    /// Sets the DEFLATE compression level used for outgoing messages, ranging
+    /// from 0 (no compression) to 9 (best compression). The default is 6.
+    ///
+    /// # Panics
+    ///
+    /// If `level` is greater than 9.
+    #[must_use]
+    pub fn compression_level(mut self, level: u32) -> Self {
+        assert!(level <= 9, "compression level must be between 0 and 9");
+        self.compression_level = level;
+
+        self
+    }
+
+    /// Sets whether the server resets its compression context after every
+    /// message. This trades compression ratio for lower memory usage.
+    ///
+    /// A client will request this from the server, a server will always
+    /// enable it. The default is `false`.
+    #[must_use]
+    pub fn server_no_context_takeover(mut self, value: bool) -> Self {
+        self.server_no_context_takeover = value;
+
+        self
+    }
+
+    /// Sets whether the client resets its compression context after every
+    /// message. This trades compression ratio for lower memory usage.
+    ///
+    /// A server will request this from the client, a client will always
+    /// enable it. The default is `false`.
+    #[must_use]
+    pub fn client_no_context_takeover(mut self, value: bool) -> Self {
+        self.client_no_context_takeover = value;
+
+        self
+    }
+
+    /// Sets the base-2 logarithm of the maximum sliding window size the server
+    /// may use for compression. Smaller windows trade compression ratio for
+    /// lower memory usage.
+    ///
+    /// A client will request this limit from the server, a server will never
+    /// exceed it. The default is 15.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is not between 9 and 15.
+    #[must_use]
+    pub fn server_max_window_bits(mut self, bits: u8) -> Self {
+        assert!(
+            (MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(&bits),
+            "window bits must be between 9 and 15"
+        );
+        self.server_max_window_bits = bits;
+
+        self
+    }
+
+    /// Sets the base-2 logarithm of the maximum sliding window size the client
+    /// may use for compression. Smaller windows trade compression ratio for
+    /// lower memory usage.
+    ///
+    /// A server will request this limit from the client, a client will never
+    /// exceed it. The default is 15.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is not between 9 and 15.
+    #[must_use]
+    pub fn client_max_window_bits(mut self, bits: u8) -> Self {
+        assert!(
+            (MIN_WINDOW_BITS..=MAX_WINDOW_BITS).contains(&bits),
+            "window bits must be between 9 and 15"
+        );
+        self.client_max_window_bits = bits;
+
+        self
+    }
+
+    /// Accepts a single offer from a client, if possible.
+    fn accept_offer(self, offer: &Params) -> Option<(Vec<Param>, DeflateCodec)> {
+        let server_max_window_bits = offer
+            .server_max_window_bits
+            .unwrap_or(MAX_WINDOW_BITS)
+            .min(self.server_max_window_bits);
+
+        if server_max_window_bits < MIN_WINDOW_BITS {
+            return None;
+        }
+
+        // We can only limit the client's window size if it signaled support for it
+        let client_max_window_bits = offer.client_max_window_bits.map(|bits| {
+            bits.unwrap_or(MAX_WINDOW_BITS)
+                .min(self.client_max_window_bits)
+        });
+        let server_no_context_takeover =
+            offer.server_no_context_takeover || self.server_no_context_takeover;
+        let client_no_context_takeover =
+            offer.client_no_context_takeover || self.client_no_context_takeover;
+
+        let mut params = Vec::new();
+
+        if server_no_context_takeover {
+            params.push(Param::new("server_no_context_takeover"));
+        }
+
+        if client_no_context_takeover {
+            params.push(Param::new("client_no_context_takeover"));
+        }
+
+        if offer.server_max_window_bits.is_some() || server_max_window_bits < MAX_WINDOW_BITS {
+            params.push(Param::with_value(
+                "server_max_window_bits",
+                server_max_window_bits.to_string(),
+            ));
+        }
+
+        if let Some(bits) = client_max_window_bits.filter(|bits| *bits < MAX_WINDOW_BITS) {
+            params.push(Param::with_value(
+                "client_max_window_bits",
+                bits.to_string(),
+            ));
+        }
+
+        Some((
+            params,
+            DeflateCodec::new(
+                self.compression_level,
+                server_max_window_bits,
+                server_no_context_takeover,
+            ),
+        ))
+    }
+}
+
+impl Extension for PerMessageDeflate {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn rsv(&self) -> Rsv {
+        Rsv::RSV1
+    }
+
+    fn offer(&self) -> Vec<Param> {
+        let mut params = Vec::new();
+
+        if self.server_no_context_takeover {
+            params.push(Param::new("server_no_context_takeover"));
+        }
+
+        if self.client_no_context_takeover {
+            params.push(Param::new("client_no_context_takeover"));
+        }
+
+        if self.server_max_window_bits < MAX_WINDOW_BITS {
+            params.push(Param::with_value(
+                "server_max_window_bits",
+                self.server_max_window_bits.to_string(),
+            ));
+        }
+
+        // Always signal that we support limiting our window size
+        if self.client_max_window_bits < MAX_WINDOW_BITS {
+            params.push(Param::with_value(
+                "client_max_window_bits",
+                self.client_max_window_bits.to_string(),
+            ));
+        } else {
+            params.push(Param::new("client_max_window_bits"));
+        }
+
+        params
+    }
+
+    fn confirm(&self, params: &[Param]) -> Option<Box<dyn NegotiatedExtension>> {
+        let params = Params::parse(params)?;
+
+        if self.server_no_context_takeover && !params.server_no_context_takeover {
+            return None;
+        }
+
+        match params.server_max_window_bits {
+            Some(bits) if bits > self.server_max_window_bits => return None,
+            None if self.server_max_window_bits < MAX_WINDOW_BITS => return None,
+            _ => {}
+        }
+
+        let window_bits = match params.client_max_window_bits {
+            None => self.client_max_window_bits,
+            Some(Some(bits)) => bits.min(self.client_max_window_bits),
+            // A value is required in the response
+            Some(None) => return None,
+        };
+
+        if window_bits < MIN_WINDOW_BITS {
+            return None;
+        }
+
+        Some(Box::new(DeflateCodec::new(
+            self.compression_level,
+            window_bits,
+            self.client_no_context_takeover || params.client_no_context_takeover,
+        )))
+    }
+
+    fn accept(&self, offers: &[&[Param]]) -> Option<(Vec<Param>, Box<dyn NegotiatedExtension>)> {
+        offers
+            .iter()
+            .filter_map(|offer| Params::parse(offer))
+            .find_map(|offer| self.accept_offer(&offer))
+            .map(|(params, codec)| (params, Box::new(codec) as Box<dyn NegotiatedExtension>))
+    }
+}
+
+impl Default for PerMessageDeflate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parsed permessage-deflate extension parameters.
+#[derive(Default)]
+struct Params {
+    /// Whether `server_no_context_takeover` is present.
+    server_no_context_takeover: bool,
+    /// Whether `client_no_context_takeover` is present.
+    client_no_context_takeover: bool,
+    /// The value of `server_max_window_bits`, if present.
+    server_max_window_bits: Option<u8>,
+    /// The value of `client_max_window_bits`, if present. The value itself is
+    /// optional in offers.
+    #[allow(clippy::option_option)]
+    client_max_window_bits: Option<Option<u8>>,
+}
+
+impl Params {
+    /// Parses a list of extension parameters, returning `None` if any of them
+    /// is unknown, duplicated or has an invalid value.
+    fn parse(params: &[Param]) -> Option<Self> {
+        let mut parsed = Self::default();
+
+        for param in params {
+            match (param.name(), param.value()) {
+                ("server_no_context_takeover", None) if !parsed.server_no_context_takeover => {
+                    parsed.server_no_context_takeover = true;
+                }
+                ("client_no_context_takeover", None) if !parsed.client_no_context_takeover => {
+                    parsed.client_no_context_takeover = true;
+                }
+                ("server_max_window_bits", Some(value))
+                    if parsed.server_max_window_bits.is_none() =>
+                {
+                    parsed.server_max_window_bits = Some(parse_window_bits(value)?);
+                }
+                ("client_max_window_bits", value) if parsed.client_max_window_bits.is_none() => {
+                    parsed.client_max_window_bits = Some(match value {
+                        Some(value) => Some(parse_window_bits(value)?),
+                        None => None,
+                    });
+                }
+                _ => return None,
+            }
+        }
+
+        Some(parsed)
+    }
+}
+
+/// Parses a window size parameter value, which must be an integer between 8
+/// and 15 without leading zeroes.
+fn parse_window_bits(value: &str) -> Option<u8> {
+    if value.starts_with('0') {
+        return None;
+    }
+
+    value
+        .parse()
+        .ok()
+        .filter(|bits| (8..=MAX_WINDOW_BITS).contains(bits))
+}
+
+/// Compression state of a connection with the permessage-deflate extension
+/// negotiated.
+#[derive(Debug)]
+pub(super) struct DeflateCodec {
+    /// Compressor for outgoing messages.
+    compress: Compress,
+    /// Whether to reset the compressor after every message.
+    compress_reset: bool,
+    /// Whether the outgoing message currently being sent is compressed.
+    compressing: bool,
+    /// Decompressor for incoming messages.
+    decompress: Decompress,
+    /// Whether the incoming message currently being received is compressed.
+    decompressing: bool,
+}
+
+impl DeflateCodec {
+    /// Creates a new codec that compresses with the given level and sliding
+    /// window size, optionally resetting the compression context after every
+    /// message.
+    fn new(level: u32, window_bits: u8, no_context_takeover: bool) -> Self {
+        Self {
+            compress: Compress::new_with_window_bits(Compression::new(level), false, window_bits),
+            compress_reset: no_context_takeover,
+            compressing: false,
+            // Decompression with the largest window supports all smaller windows
+            decompress: Decompress::new(false),
+            decompressing: false,
+        }
+    }
+
+    /// Decompresses `input` and appends it to `output`, failing if `output`
+    /// would grow beyond `max_len` bytes.
+    fn decompress(
+        &mut self,
+        mut input: &[u8],
+        output: &mut Vec<u8>,
+        max_len: usize,
+    ) -> Result<(), Error> {
+        loop {
+            if output.len() == output.capacity() {
+                // Allow one more byte than permitted to detect overly large payloads
+                let remaining = max_len.saturating_sub(output.len()).saturating_add(1);
+                output.reserve(
+                    output
+                        .len()
+                        .max(input.len().saturating_mul(2))
+                        .max(1024)
+                        .min(remaining),
+                );
+            }
+
+            let total_in = self.decompress.total_in();
+            let total_out = self.decompress.total_out();
+            let status = self
+                .decompress
+                .decompress_vec(input, output, FlushDecompress::Sync)
+                .map_err(|_| ProtocolError::InvalidCompressedData)?;
+            // SAFETY: The decompressor never consumes more bytes than available
+            input = unsafe {
+                input.get_unchecked(
+                    usize::try_from(self.decompress.total_in() - total_in).unwrap_unchecked()..,
+                )
+            };
+
+            if output.len() > max_len {
+                return Err(Error::PayloadTooLong {
+                    len: output.len(),
+                    max_len,
+                });
+            }
+
+            if status == Status::StreamEnd {
+                // The peer may end a message with a final DEFLATE block, any following data
+                // starts a new DEFLATE stream
+                self.decompress.reset(false);
+            } else if output.len() < output.capacity() {
+                if input.is_empty() {
+                    return Ok(());
+                } else if self.decompress.total_in() == total_in
+                    && self.decompress.total_out() == total_out
+                {
+                    return Err(Error::Protocol(ProtocolError::InvalidCompressedData));
+                }
+            }
+
+            if input.is_empty() && output.len() < output.capacity() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl NegotiatedExtension for DeflateCodec {
+    /// Compresses the payload of an outgoing data frame.
+    fn encode(&mut self, mut frame: Frame) -> Frame {
+        match frame.opcode {
+            OpCode::Text | OpCode::Binary => {
+                self.compressing = true;
+                frame.rsv |= Rsv::RSV1;
+            }
+            OpCode::Continuation if self.compressing => {}
+            _ => return frame,
+        }
+
+        let mut input: &[u8] = &frame.payload;
+        let mut output = Vec::with_capacity(input.len() / 2 + 64);
+
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(output.capacity());
+            }
+
+            let total_in = self.compress.total_in();
+            self.compress
+                .compress_vec(input, &mut output, FlushCompress::Sync)
+                .expect("compressing into a buffer cannot fail");
+            // SAFETY: The compressor never consumes more bytes than available
+            input = unsafe {
+                input.get_unchecked(
+                    usize::try_from(self.compress.total_in() - total_in).unwrap_unchecked()..,
+                )
+            };
+
+            // The flush is complete if there is space left in the output
+            if input.is_empty() && output.len() < output.capacity() {
+                break;
+            }
+        }
+
+        if frame.is_final {
+            if output.ends_with(&TRAILER) {
+                output.truncate(output.len() - TRAILER.len());
+            } else if output.is_empty() {
+                // Nothing was compressed since the last flush, which leaves no trailer to
+                // remove. A single empty stored block is sent instead (RFC 7692, 7.2.3.6)
+                output.push(0x00);
+            }
+
+            if self.compress_reset {
+                self.compress.reset();
+            }
+
+            self.compressing = false;
+        }
+
+        frame.payload = Payload::from(output);
+
+        frame
+    }
+
+    /// Decompresses the payload of an incoming frame if it is part of a
+    /// compressed message. The decompressed payload may not exceed `max_len`
+    /// bytes.
+    fn decode(&mut self, mut frame: Frame, max_len: usize) -> Result<Frame, Error> {
+        match frame.opcode {
+            _ if frame.opcode.is_control() => {
+                if frame.rsv.contains(Rsv::RSV1) {
+                    return Err(Error::Protocol(ProtocolError::InvalidRsv));
+                }
+
+                return Ok(frame);
+            }
+            OpCode::Continuation => {
+                if frame.rsv.contains(Rsv::RSV1) {
+                    return Err(Error::Protocol(ProtocolError::InvalidRsv));
+                }
+            }
+            _ => {
+                self.decompressing = frame.rsv.contains(Rsv::RSV1);
+            }
+        }
+
+        if !self.decompressing {
+            return Ok(frame);
+        }
+
+        let mut output = Vec::new();
+        self.decompress(&frame.payload, &mut output, max_len)?;
+
+        if frame.is_final {
+            self.decompress(&TRAILER, &mut output, max_len)?;
+            self.decompressing = false;
+        }
+
+        frame.rsv = frame.rsv.difference(Rsv::RSV1);
+        frame.payload = Payload::from(output);
+
+        Ok(frame)
+    }
+}
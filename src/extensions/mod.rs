@@ -0,0 +1,547 @@
+//! Support for WebSocket extensions, which are negotiated via the
+//! `Sec-WebSocket-Extensions` header and transform the frames of a connection.
+//!
+//! Custom extensions can be implemented via the [`Extension`] trait.
+use std::fmt;
+
+#[cfg(feature = "permessage-deflate")]
+pub use self::deflate::PerMessageDeflate;
+use crate::{
+    proto::{Frame, OpCode, Rsv},
+    utf8::Validator,
+    Error,
+};
+
+#[cfg(feature = "permessage-deflate")]
+mod deflate;
+
+/// A WebSocket extension that can be negotiated during the opening handshake.
+///
+/// Extensions are configured on the client and server builders. Clients offer
+/// them to the server in the order they were added and servers accept the
+/// offers of those they support, again in the order they were added to the
+/// server builder. Once negotiated, the extension creates a
+/// [`NegotiatedExtension`] for the connection, which transforms the frames
+/// sent and received on it.
+///
+/// Every extension may claim RSV bits. Frames with RSV bits set are only
+/// accepted if a negotiated extension claimed them, and two extensions that
+/// claim the same bits can not be negotiated together.
+pub trait Extension: Send + Sync {
+    /// The name of the extension in the `Sec-WebSocket-Extensions` header.
+    fn name(&self) -> &str;
+
+    /// The RSV bits that are reserved by this extension.
+    fn rsv(&self) -> Rsv;
+
+    /// Returns the parameters a client offers the extension with.
+    fn offer(&self) -> Vec<Param>;
+
+    /// Validates the parameters a server accepted the offer of a client with
+    /// and creates the state of the extension for the connection.
+    ///
+    /// Returning `None` fails the handshake.
+    fn confirm(&self, params: &[Param]) -> Option<Box<dyn NegotiatedExtension>>;
+
+    /// Picks one of the offers for this extension made by a client, in the
+    /// order they were made in. Returns the parameters to respond with and the
+    /// state of the extension for the connection.
+    ///
+    /// Returning `None` declines all offers and the extension is not used.
+    fn accept(&self, offers: &[&[Param]]) -> Option<(Vec<Param>, Box<dyn NegotiatedExtension>)>;
+}
+
+/// The state of an [`Extension`] negotiated for a connection.
+///
+/// Outgoing frames are passed through all negotiated extensions in the order
+/// they were negotiated in, incoming frames in reverse order. Extensions see
+/// all frames, including control frames and frames without any of their RSV
+/// bits set.
+pub trait NegotiatedExtension: Send + Sync {
+    /// Transforms an outgoing frame.
+    fn encode(&mut self, frame: Frame) -> Frame;
+
+    /// Reverses the transformation of an incoming frame. The payload of the
+    /// returned frame may not exceed `max_len` bytes.
+    ///
+    /// Text messages that had RSV bits set are validated to be UTF-8 after all
+    /// extensions decoded them.
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the connection. For [`Error::Protocol`] and
+    /// [`Error::PayloadTooLong`], a matching close frame is sent to the peer.
+    fn decode(&mut self, frame: Frame, max_len: usize) -> Result<Frame, Error>;
+}
+
+/// A parameter of an extension in the `Sec-WebSocket-Extensions` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    /// The name of the parameter.
+    name: String,
+    /// The optional value of the parameter.
+    value: Option<String>,
+}
+
+impl Param {
+    /// Creates a new parameter without a value.
+    ///
+    /// # Panics
+    ///
+    /// If `name` is not a valid HTTP token.
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        let name = name.into();
+        assert!(is_token(&name), "parameter name must be a valid token");
+
+        Self { name, value: None }
+    }
+
+    /// Creates a new parameter with a value.
+    ///
+    /// # Panics
+    ///
+    /// If `name` or `value` are not valid HTTP tokens.
+    pub fn with_value<N: Into<String>, V: Into<String>>(name: N, value: V) -> Self {
+        let name = name.into();
+        let value = value.into();
+        assert!(is_token(&name), "parameter name must be a valid token");
+        assert!(is_token(&value), "parameter value must be a valid token");
+
+        Self {
+            name,
+            value: Some(value),
+        }
+    }
+
+    /// The name of the parameter.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the parameter, if any.
+    #[must_use]
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+}
+
+/// The extensions that a client offers or a server is willing to accept
+/// during the opening handshake, in order.
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Default)]
+pub(crate) struct ExtensionConfig(Vec<Box<dyn Extension>>);
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl ExtensionConfig {
+    /// Adds an extension to the end of the list.
+    pub fn push(&mut self, extension: Box<dyn Extension>) {
+        self.0.push(extension);
+    }
+
+    /// Returns the value of the `Sec-WebSocket-Extensions` header a client
+    /// sends to offer the configured extensions, if any are configured.
+    #[cfg(feature = "client")]
+    pub fn offer(&self) -> Option<String> {
+        let offers: Vec<String> = self
+            .0
+            .iter()
+            .map(|extension| format_extension(extension.name(), &extension.offer()))
+            .collect();
+
+        (!offers.is_empty()).then(|| offers.join(", "))
+    }
+
+    /// Validates the extensions accepted by a server in the
+    /// `Sec-WebSocket-Extensions` header values of its response and sets them
+    /// up for use on the connection.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the server accepted an extension that was not
+    /// offered, accepted an extension twice, accepted extensions that claim
+    /// the same RSV bits or the parameters of an accepted extension are
+    /// invalid.
+    #[cfg(feature = "client")]
+    pub fn confirm<'a>(
+        &self,
+        header_values: impl Iterator<Item = &'a [u8]>,
+    ) -> Result<Extensions, crate::upgrade::Error> {
+        let mut extensions = Extensions::default();
+        let mut confirmed = Vec::new();
+
+        for value in header_values {
+            let accepted = std::str::from_utf8(value)
+                .ok()
+                .and_then(parse_header)
+                .ok_or(crate::upgrade::Error::InvalidExtension)?;
+
+            for header in accepted {
+                let idx = self
+                    .0
+                    .iter()
+                    .position(|extension| extension.name() == header.name)
+                    .filter(|idx| !confirmed.contains(idx))
+                    .ok_or(crate::upgrade::Error::InvalidExtension)?;
+                let extension = &self.0[idx];
+                let negotiated = extension
+                    .confirm(&header.params)
+                    .ok_or(crate::upgrade::Error::InvalidExtension)?;
+
+                if !extensions.push(extension.rsv(), negotiated) {
+                    return Err(crate::upgrade::Error::InvalidExtension);
+                }
+
+                confirmed.push(idx);
+            }
+        }
+
+        Ok(extensions)
+    }
+
+    /// Picks the extensions to use from the ones offered by a client in the
+    /// `Sec-WebSocket-Extensions` header values of its request.
+    ///
+    /// Returns the value of the `Sec-WebSocket-Extensions` header to respond
+    /// with, if any extension was accepted, and the negotiated extensions.
+    /// Malformed offers are ignored.
+    #[cfg(feature = "server")]
+    pub fn accept<'a>(
+        &self,
+        header_values: impl Iterator<Item = &'a [u8]>,
+    ) -> (Option<String>, Extensions) {
+        let mut extensions = Extensions::default();
+
+        if self.0.is_empty() {
+            return (None, extensions);
+        }
+
+        let offers: Vec<ExtensionHeader> = header_values
+            .filter_map(|value| std::str::from_utf8(value).ok().and_then(parse_header))
+            .flatten()
+            .collect();
+        let mut response = Vec::new();
+
+        for extension in &self.0 {
+            let offered: Vec<&[Param]> = offers
+                .iter()
+                .filter(|offer| offer.name == extension.name())
+                .map(|offer| offer.params.as_slice())
+                .collect();
+
+            // Extensions that claim RSV bits of an already accepted extension are skipped
+            if offered.is_empty() || extensions.rsv.intersects(extension.rsv()) {
+                continue;
+            }
+
+            if let Some((params, negotiated)) = extension.accept(&offered) {
+                response.push(format_extension(extension.name(), &params));
+                extensions.push(extension.rsv(), negotiated);
+            }
+        }
+
+        (
+            (!response.is_empty()).then(|| response.join(", ")),
+            extensions,
+        )
+    }
+}
+
+/// The state of the extensions negotiated for a connection.
+pub(crate) struct Extensions {
+    /// The negotiated extensions, in order.
+    negotiated: Vec<Box<dyn NegotiatedExtension>>,
+    /// The RSV bits claimed by the negotiated extensions.
+    rsv: Rsv,
+    /// Whether the incoming message currently being received is a text
+    /// message that has to be validated after decoding.
+    validating_text: bool,
+    /// UTF-8 validator for decoded text messages.
+    validator: Validator,
+}
+
+impl Extensions {
+    /// Adds a negotiated extension that claims the given RSV bits. Returns
+    /// `false` if the bits are already claimed by another extension.
+    #[cfg(any(feature = "client", feature = "server"))]
+    fn push(&mut self, rsv: Rsv, negotiated: Box<dyn NegotiatedExtension>) -> bool {
+        if self.rsv.intersects(rsv) {
+            return false;
+        }
+
+        self.rsv |= rsv;
+        self.negotiated.push(negotiated);
+
+        true
+    }
+
+    /// Whether no extensions were negotiated.
+    pub fn is_empty(&self) -> bool {
+        self.negotiated.is_empty()
+    }
+
+    /// The RSV bits that frames are allowed to carry with the negotiated
+    /// extensions.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub fn rsv(&self) -> Rsv {
+        self.rsv
+    }
+
+    /// Applies the negotiated extensions to an outgoing frame.
+    pub fn encode(&mut self, mut frame: Frame) -> Frame {
+        for extension in &mut self.negotiated {
+            frame = extension.encode(frame);
+        }
+
+        frame
+    }
+
+    /// Reverses the negotiated extensions on an incoming frame. The decoded
+    /// payload of the frame may not exceed `max_len` bytes.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if an extension fails to decode the frame or the
+    /// decoded payload of a text message is not valid UTF-8.
+    pub fn decode(&mut self, mut frame: Frame, max_len: usize) -> Result<Frame, Error> {
+        if self.negotiated.is_empty() {
+            return Ok(frame);
+        }
+
+        // Text messages with RSV bits set are not validated by the codec
+        let validate = match frame.opcode {
+            OpCode::Text => {
+                self.validating_text = !frame.rsv.is_empty();
+                self.validator.reset();
+                self.validating_text
+            }
+            OpCode::Binary => {
+                self.validating_text = false;
+                false
+            }
+            OpCode::Continuation => self.validating_text,
+            _ => false,
+        };
+
+        for extension in self.negotiated.iter_mut().rev() {
+            frame = extension.decode(frame, max_len)?;
+        }
+
+        if validate {
+            self.validator.feed(&frame.payload, frame.is_final)?;
+            frame
+                .payload
+                .set_utf8_validated(frame.opcode == OpCode::Text && frame.is_final);
+        }
+
+        Ok(frame)
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl Default for Extensions {
+    fn default() -> Self {
+        Self {
+            negotiated: Vec::new(),
+            rsv: Rsv::NONE,
+            validating_text: false,
+            validator: Validator::new(),
+        }
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("negotiated", &self.negotiated.len())
+            .field("rsv", &self.rsv)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+/// Formats an extension with its parameters for use in the
+/// `Sec-WebSocket-Extensions` header.
+fn format_extension(name: &str, params: &[Param]) -> String {
+    let mut formatted = String::from(name);
+
+    for param in params {
+        formatted.push_str("; ");
+        formatted.push_str(&param.name);
+
+        if let Some(value) = &param.value {
+            formatted.push('=');
+            formatted.push_str(value);
+        }
+    }
+
+    formatted
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+/// A single extension, as offered by a client or accepted by a server in the
+/// `Sec-WebSocket-Extensions` header.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ExtensionHeader {
+    /// The name of the extension.
+    pub name: String,
+    /// The parameters of the extension, in order.
+    pub params: Vec<Param>,
+}
+
+/// Returns whether a byte is a valid `tchar` as defined in RFC 7230.
+fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Returns whether a string is a valid, non-empty `token` as defined in RFC
+/// 7230, which extension names and parameters as well as subprotocol names
+/// must be.
+pub(crate) fn is_token(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(is_token_char)
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+/// Splits `value` at every occurrence of `separator` that is not inside of a
+/// quoted string.
+fn split_unquoted(value: &str, separator: u8) -> impl Iterator<Item = &str> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    let mut bytes = value.bytes().enumerate();
+
+    std::iter::from_fn(move || {
+        if start > value.len() {
+            return None;
+        }
+
+        for (idx, b) in bytes.by_ref() {
+            if escaped {
+                escaped = false;
+            } else if in_quotes && b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_quotes = !in_quotes;
+            } else if !in_quotes && b == separator {
+                let part = &value[start..idx];
+                start = idx + 1;
+                return Some(part);
+            }
+        }
+
+        let part = &value[start..];
+        start = value.len() + 1;
+        Some(part)
+    })
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+/// Parses a parameter value, which is either a token or a quoted string whose
+/// unquoted content is a token.
+fn parse_param_value(value: &str) -> Option<String> {
+    let unquoted = if let Some(quoted) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        let mut unquoted = String::with_capacity(quoted.len());
+        let mut chars = quoted.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unquoted.push(chars.next()?),
+                '"' => return None,
+                c => unquoted.push(c),
+            }
+        }
+
+        unquoted
+    } else {
+        value.to_owned()
+    };
+
+    // RFC 6455 requires the value to be a token, even when quoted
+    is_token(&unquoted).then_some(unquoted)
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+/// Parses the value of a `Sec-WebSocket-Extensions` header into the list of
+/// extensions it contains, in order.
+///
+/// Returns `None` if the header value is malformed.
+pub(crate) fn parse_header(value: &str) -> Option<Vec<ExtensionHeader>> {
+    let mut extensions = Vec::new();
+
+    for extension in split_unquoted(value, b',') {
+        let mut parts = split_unquoted(extension, b';');
+        let name = parts.next()?.trim();
+
+        if !is_token(name) {
+            return None;
+        }
+
+        let mut params = Vec::new();
+
+        for param in parts {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(parse_param_value(value.trim())?)),
+                None => (param.trim(), None),
+            };
+
+            if !is_token(name) {
+                return None;
+            }
+
+            params.push(Param {
+                name: name.to_owned(),
+                value,
+            });
+        }
+
+        extensions.push(ExtensionHeader {
+            name: name.to_owned(),
+            params,
+        });
+    }
+
+    Some(extensions)
+}
+
+#[cfg(all(test, any(feature = "client", feature = "server")))]
+mod tests {
+    use super::{parse_header, ExtensionHeader, Param};
+
+    #[test]
+    fn parse_extensions_header() {
+        let parsed = parse_header(
+            "permessage-deflate; client_max_window_bits, permessage-deflate; \
+             server_max_window_bits=\"10\" ;server_no_context_takeover,x-custom",
+        )
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            [
+                ExtensionHeader {
+                    name: "permessage-deflate".to_owned(),
+                    params: vec![Param::new("client_max_window_bits")],
+                },
+                ExtensionHeader {
+                    name: "permessage-deflate".to_owned(),
+                    params: vec![
+                        Param::with_value("server_max_window_bits", "10"),
+                        Param::new("server_no_context_takeover"),
+                    ],
+                },
+                ExtensionHeader {
+                    name: "x-custom".to_owned(),
+                    params: Vec::new(),
+                },
+            ]
+        );
+
+        assert!(parse_header("").is_none());
+        assert!(parse_header("permessage-deflate;").is_none());
+        assert!(parse_header("permessage-deflate; a=\"b").is_none());
+        assert!(parse_header("foo, , bar").is_none());
+    }
+}
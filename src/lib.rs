@@ -41,7 +41,9 @@ compile_error!("client and server implementation require at least one SHA1 backe
 
 #[cfg(feature = "client")]
 pub mod client;
+pub mod codec;
 pub mod error;
+pub mod extensions;
 mod mask;
 pub mod proto;
 #[cfg(feature = "client")]
@@ -60,7 +62,10 @@ mod utf8;
 #[cfg(feature = "client")]
 pub use client::Builder as ClientBuilder;
 pub use error::Error;
-pub use proto::{CloseCode, Config, Limits, Message, Payload, WebSocketStream};
+pub use proto::{
+    CloseCode, CloseFrame, Config, EncodedMessage, Limits, Message, Payload, Utf8Payload,
+    WebSocketStream,
+};
 #[cfg(feature = "server")]
 pub use server::Builder as ServerBuilder;
 pub use tls::{Connector, MaybeTlsStream};
@@ -11,7 +11,7 @@ use std::hint::unreachable_unchecked;
 use bytes::{Buf, BytesMut};
 use tokio_util::codec::Decoder;
 
-use super::types::{Frame, Limits, OpCode, Role};
+use super::types::{Frame, Limits, OpCode, Role, Rsv};
 use crate::{
     mask,
     proto::ProtocolError,
@@ -33,8 +33,19 @@ pub(super) struct WebSocketProtocol {
     pub(super) role: Role,
     /// The [`Limits`] imposed on this stream.
     pub(super) limits: Limits,
+    /// RSV bits that are reserved by negotiated extensions and therefore
+    /// allowed to be set.
+    allowed_rsv: Rsv,
     /// Opcode of the full message.
     fragmented_message_opcode: OpCode,
+    /// RSV bits of the first frame of the full message.
+    fragmented_message_rsv: Rsv,
+    /// Opcode of the message that the last data frame belonged to.
+    message_opcode: OpCode,
+    /// Payload length of the message received so far.
+    message_len: usize,
+    /// Number of frames of the message received so far.
+    message_fragments: usize,
     /// Index up to which the payload was processed (unmasked and validated).
     payload_processed: usize,
     /// UTF-8 validator.
@@ -44,15 +55,26 @@ pub(super) struct WebSocketProtocol {
 impl WebSocketProtocol {
     /// Creates a new WebSocket codec.
     #[cfg(any(feature = "client", feature = "server"))]
-    pub(super) fn new(role: Role, limits: Limits) -> Self {
+    pub(super) fn new(role: Role, limits: Limits, allowed_rsv: Rsv) -> Self {
         Self {
             role,
             limits,
+            allowed_rsv,
             fragmented_message_opcode: OpCode::Continuation,
+            fragmented_message_rsv: Rsv::NONE,
+            message_opcode: OpCode::Continuation,
+            message_len: 0,
+            message_fragments: 0,
             payload_processed: 0,
             validator: Validator::new(),
         }
     }
+
+    /// The maximum payload length of the message that the last decoded data
+    /// frame belonged to.
+    pub(super) fn max_message_len(&self) -> usize {
+        self.limits.max_message_len(self.message_opcode)
+    }
 }
 
 /// Macro that returns `Ok(None)` early and reserves missing capacity if buf is
@@ -84,9 +106,9 @@ impl Decoder for WebSocketProtocol {
         let fin = fin_and_rsv >> 7 != 0;
 
         // Bits 1-3
-        let rsv = fin_and_rsv & 0x70;
+        let rsv = Rsv::from_header_byte(*fin_and_rsv);
 
-        if rsv != 0 {
+        if !self.allowed_rsv.contains(rsv) {
             return Err(Error::Protocol(ProtocolError::InvalidRsv));
         }
 
@@ -155,10 +177,33 @@ impl Decoder for WebSocketProtocol {
             }
         }
 
-        if payload_length > self.limits.max_payload_len {
-            return Err(Error::PayloadTooLong {
+        if payload_length > self.limits.max_frame_len {
+            return Err(Error::FrameTooLong {
                 len: payload_length,
-                max_len: self.limits.max_payload_len,
+                max_len: self.limits.max_frame_len,
+            });
+        }
+
+        // Limits of data frames apply to the full message they belong to
+        let (message_opcode, message_len, message_fragments) = if opcode == OpCode::Continuation {
+            (
+                self.fragmented_message_opcode,
+                self.message_len.saturating_add(payload_length),
+                self.message_fragments + 1,
+            )
+        } else {
+            (opcode, payload_length, 1)
+        };
+        let max_len = self.limits.max_message_len(message_opcode);
+
+        if message_len > max_len {
+            return Err(Error::PayloadTooLong {
+                len: message_len,
+                max_len,
+            });
+        } else if message_fragments > self.limits.max_fragments {
+            return Err(Error::TooManyFragments {
+                max_fragments: self.limits.max_fragments,
             });
         }
 
@@ -169,10 +214,19 @@ impl Decoder for WebSocketProtocol {
             ensure_buffer_has_space!(src, offset);
         }
 
+        // The payload of messages that are transformed by an extension can only be
+        // validated after the extension decoded it
+        let message_rsv = if opcode == OpCode::Continuation {
+            self.fragmented_message_rsv
+        } else {
+            rsv
+        };
+
         if payload_length != 0 {
-            let is_text = opcode == OpCode::Text
-                || (opcode == OpCode::Continuation
-                    && self.fragmented_message_opcode == OpCode::Text);
+            let is_text = message_rsv.is_empty()
+                && (opcode == OpCode::Text
+                    || (opcode == OpCode::Continuation
+                        && self.fragmented_message_opcode == OpCode::Text));
             let payload_available = src.len() - offset;
 
             if payload_length > payload_available {
@@ -260,7 +314,7 @@ impl Decoder for WebSocketProtocol {
         src.advance(offset);
         // Take the payload
         let mut payload = Payload::from(src.split_to(payload_length));
-        payload.set_utf8_validated(opcode == OpCode::Text && fin);
+        payload.set_utf8_validated(opcode == OpCode::Text && fin && rsv.is_empty());
 
         // It is possible to receive intermediate control frames between a large other
         // frame. We therefore can't simply reset the fragmented opcode after we receive
@@ -269,16 +323,24 @@ impl Decoder for WebSocketProtocol {
             // Full chunked message received (and opcode is Continuation)
             // or first frame of a multi-frame message received
             self.fragmented_message_opcode = opcode;
+            self.fragmented_message_rsv = rsv;
         }
         // In all other cases, we have either a continuation or control frame, neither
         // of which change change the opcode being assembled
 
+        if !opcode.is_control() {
+            self.message_opcode = message_opcode;
+            self.message_len = message_len;
+            self.message_fragments = message_fragments;
+        }
+
         self.payload_processed = 0;
 
         Ok(Some(Frame {
             opcode,
             payload,
             is_final: fin,
+            rsv,
         }))
     }
 }
@@ -0,0 +1,804 @@
+//! Protocol state of a connection that is shared by [`WebSocketStream`] and
+//! the halves it can be split into.
+//!
+//! [`WebSocketStream`]: super::WebSocketStream
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io::{self, IoSlice},
+    mem::{replace, take},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::BytesMut;
+use tokio::{
+    io::AsyncWrite,
+    time::{sleep_until, Instant, Sleep},
+};
+
+use super::{
+    error::ProtocolError,
+    keepalive::KeepaliveEvent,
+    types::{CloseFrame, EncodedMessage, Frame, Message, OpCode, Payload, Role, StreamState},
+    Config,
+};
+use crate::{
+    extensions::Extensions,
+    utf8::{self, Validator},
+    CloseCode, Error,
+};
+
+/// Maximum number of buffers passed to a single vectored write, which stays
+/// below `IOV_MAX` of all common platforms.
+const MAX_IO_SLICES: usize = 512;
+
+/// Size of the buffer that payloads of outgoing frames are masked into while
+/// writing them.
+const MASK_BUF_SIZE: usize = 16 * 1024;
+
+/// A part of a frame that is about to be written, either borrowed from the
+/// frame or masked into the mask buffer of the connection.
+#[derive(Clone, Copy)]
+enum Chunk<'a> {
+    /// Bytes that are written as is.
+    Unmasked(&'a [u8]),
+    /// Range of the mask buffer holding masked payload bytes.
+    Masked(usize, usize),
+}
+
+impl<'a> Chunk<'a> {
+    /// Returns the bytes of the chunk.
+    fn get<'b>(self, mask_buf: &'b [u8]) -> &'b [u8]
+    where
+        'a: 'b,
+    {
+        match self {
+            Self::Unmasked(buf) => buf,
+            // SAFETY: Ranges are only created within the filled mask buffer
+            Self::Masked(start, end) => unsafe { mask_buf.get_unchecked(start..end) },
+        }
+    }
+}
+
+/// Helper struct for storing a frame header, the header size and payload.
+#[derive(Debug)]
+struct EncodedFrame {
+    /// Encoded frame header.
+    header: [u8; 10],
+    /// Length of the header.
+    header_len: u8,
+    /// Mask that the payload is masked with while writing it.
+    mask: Option<[u8; 4]>,
+    /// Message payload, which is masked when writing it to the I/O.
+    payload: Payload,
+    /// Whether this is a pong that was queued automatically in reply to a
+    /// ping and may be replaced by the reply to a later ping.
+    is_ping_reply: bool,
+}
+
+impl EncodedFrame {
+    /// Total length of the frame on the wire.
+    fn len(&self) -> usize {
+        self.header_len as usize + self.mask.map_or(0, |mask| mask.len()) + self.payload.len()
+    }
+
+    /// The header, mask and payload of the frame, in the order they are
+    /// written.
+    fn parts(&self) -> [&[u8]; 3] {
+        // SAFETY: header_len is at most the length of the header buffer
+        let header = unsafe { self.header.get_unchecked(..self.header_len as usize) };
+        let mask = self
+            .mask
+            .as_ref()
+            .map(<[u8; 4]>::as_slice)
+            .unwrap_or_default();
+
+        [header, mask, &self.payload]
+    }
+}
+
+/// The state of a connection that reading and writing both depend on: the
+/// close handshake, negotiated extensions and the queue of outgoing frames.
+#[derive(Debug)]
+pub(super) struct Connection {
+    /// The role of this end of the connection.
+    role: Role,
+
+    /// Configuration for the stream.
+    pub(super) config: Config,
+
+    /// Extensions negotiated for the stream.
+    extensions: Extensions,
+    /// Subprotocol negotiated for the stream.
+    subprotocol: Option<String>,
+
+    /// The [`StreamState`] of the current stream.
+    pub(super) state: StreamState,
+    /// When the close handshake times out, once the connection started
+    /// closing.
+    close_deadline: Option<Instant>,
+    /// The close frame received from the peer, if any.
+    peer_close: Option<CloseFrame>,
+
+    /// Buffer that outgoing frame headers are formatted into.
+    header_buf: [u8; 10],
+
+    /// Queue of outgoing frames to send.
+    frame_queue: VecDeque<EncodedFrame>,
+    /// Amount of partial bytes written of the first frame in the queue.
+    bytes_written: usize,
+    /// Total amount of bytes remaining to be sent in the frame queue.
+    pending_bytes: usize,
+    /// Buffer that payloads are masked into while writing, allocated once the
+    /// first masked payload is written.
+    mask_buf: Vec<u8>,
+    /// Opcode of the outgoing message that is being sent in frames, or
+    /// [`OpCode::Continuation`] if no fragmented message is in progress.
+    outgoing_message_opcode: OpCode,
+    /// UTF-8 validator for outgoing text frames that are sent one by one.
+    outgoing_validator: Validator,
+}
+
+impl Connection {
+    /// Creates the state of a new, active connection.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(super) fn new(
+        role: Role,
+        config: Config,
+        extensions: Extensions,
+        subprotocol: Option<String>,
+    ) -> Self {
+        Self {
+            role,
+            config,
+            extensions,
+            subprotocol,
+            state: StreamState::Active,
+            close_deadline: None,
+            peer_close: None,
+            header_buf: [0; 10],
+            frame_queue: VecDeque::with_capacity(1),
+            bytes_written: 0,
+            pending_bytes: 0,
+            mask_buf: Vec::new(),
+            outgoing_message_opcode: OpCode::Continuation,
+            outgoing_validator: Validator::new(),
+        }
+    }
+
+    /// The close frame received from the peer, if it closed the connection.
+    pub(super) fn peer_close_frame(&self) -> Option<&CloseFrame> {
+        self.peer_close.as_ref()
+    }
+
+    /// The subprotocol negotiated in the handshake, if any.
+    pub(super) fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
+    /// Whether any extensions were negotiated.
+    pub(super) fn has_extensions(&self) -> bool {
+        !self.extensions.is_empty()
+    }
+
+    /// Total amount of bytes remaining to be sent in the frame queue.
+    pub(super) fn pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    /// The capacity of the buffer that payloads are masked into.
+    pub(super) fn mask_buf_capacity(&self) -> usize {
+        self.mask_buf.capacity()
+    }
+
+    /// Whether there are frames in the queue that have not been fully written.
+    pub(super) fn has_pending_frames(&self) -> bool {
+        !self.frame_queue.is_empty()
+    }
+
+    /// Processes a frame or error read from the peer: applies the negotiated
+    /// extensions to the frame, advances the close handshake and queues
+    /// replies to pings, close frames and protocol violations.
+    ///
+    /// Frames received after the close handshake completed or the connection
+    /// was terminated, for example by a timer of the other half of a split
+    /// stream, are passed through without any effect on the connection.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if `frame` is an error or decoding the
+    /// frame with the negotiated extensions fails.
+    pub(super) fn receive(
+        &mut self,
+        frame: Result<Frame, Error>,
+        max_len: usize,
+    ) -> Result<Frame, Error> {
+        let frame = match frame.and_then(|frame| self.extensions.decode(frame, max_len)) {
+            Ok(frame) => frame,
+            Err(e) => {
+                // A timer may have terminated the connection outside of reading, which
+                // must not be undone by starting to close it again
+                if matches!(
+                    self.state,
+                    StreamState::CloseAcknowledged | StreamState::Terminated
+                ) {
+                    return Err(e);
+                } else if self.state == StreamState::ClosedByUs {
+                    self.state = StreamState::CloseAcknowledged;
+                } else {
+                    self.set_closing(StreamState::ClosedByPeer);
+
+                    match &e {
+                        Error::Protocol(e) => self.queue_frame(Frame::from(e)),
+                        Error::PayloadTooLong { max_len, .. } => self.queue_frame(
+                            Message::close(
+                                Some(CloseCode::MESSAGE_TOO_BIG),
+                                &format!("max length: {max_len}"),
+                            )
+                            .into(),
+                        ),
+                        Error::FrameTooLong { max_len, .. } => self.queue_frame(
+                            Message::close(
+                                Some(CloseCode::MESSAGE_TOO_BIG),
+                                &format!("max frame length: {max_len}"),
+                            )
+                            .into(),
+                        ),
+                        Error::TooManyFragments { max_fragments } => self.queue_frame(
+                            Message::close(
+                                Some(CloseCode::POLICY_VIOLATION),
+                                &format!("max fragments: {max_fragments}"),
+                            )
+                            .into(),
+                        ),
+                        _ => {}
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        if frame.opcode == OpCode::Close {
+            self.peer_close = Some(CloseFrame::from_validated(&frame.payload));
+        }
+
+        match frame.opcode {
+            OpCode::Close => match self.state {
+                StreamState::Active => {
+                    self.set_closing(StreamState::ClosedByPeer);
+
+                    let mut frame = frame.clone();
+                    frame.payload.truncate(2);
+
+                    self.queue_frame(frame);
+                }
+                // The close handshake is already over or the connection was torn down,
+                // which the read half of a split stream may only notice afterwards
+                StreamState::ClosedByPeer
+                | StreamState::CloseAcknowledged
+                | StreamState::Terminated => {}
+                StreamState::ClosedByUs => {
+                    self.state = StreamState::CloseAcknowledged;
+                }
+            },
+            OpCode::Ping if self.state == StreamState::Active && self.config.auto_pong => {
+                let mut frame = frame.clone();
+                frame.opcode = OpCode::Pong;
+
+                self.queue_ping_reply(frame);
+            }
+            _ => {}
+        }
+
+        Ok(frame)
+    }
+
+    /// Moves the connection into a closing state and starts the timeout of the
+    /// close handshake if the connection was active until now.
+    fn set_closing(&mut self, state: StreamState) {
+        if self.state == StreamState::Active {
+            self.close_deadline = self
+                .config
+                .close_timeout
+                .map(|timeout| Instant::now() + timeout);
+        }
+
+        self.state = state;
+    }
+
+    /// Waits for the timeout of the close handshake to expire and terminates
+    /// the connection once it does. `timer` is created once the connection
+    /// started closing. Never completes if no close timeout is configured or
+    /// the close handshake completed.
+    pub(super) fn poll_close_timeout(
+        &mut self,
+        timer: &mut Option<Pin<Box<Sleep>>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        let Some(deadline) = self
+            .close_deadline
+            .filter(|_| self.state != StreamState::CloseAcknowledged)
+        else {
+            return Poll::Pending;
+        };
+
+        let timer = timer.get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+        ready!(timer.as_mut().poll(cx));
+        self.state = StreamState::Terminated;
+
+        Poll::Ready(())
+    }
+
+    /// Applies an event of the keepalive timer: queues a due ping or closes
+    /// the connection if the peer did not respond to a ping in time. When
+    /// closing, a close frame is queued, but no reply to it is awaited. Events
+    /// are ignored once the connection is no longer active.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::KeepaliveTimeout`] if the peer timed out.
+    pub(super) fn keepalive(&mut self, event: KeepaliveEvent) -> Result<(), Error> {
+        if self.state != StreamState::Active {
+            return Ok(());
+        }
+
+        match event {
+            KeepaliveEvent::Ping(frame) => self.queue_frame(frame),
+            KeepaliveEvent::Timeout => {
+                self.queue_frame(
+                    Message::close(Some(CloseCode::GOING_AWAY), "keepalive timeout").into(),
+                );
+                self.state = StreamState::Terminated;
+
+                return Err(Error::KeepaliveTimeout);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queues a message for sending, split into frames of the configured frame
+    /// size.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the connection was
+    /// closed or [`ProtocolError::InvalidOpcode`] if a text or binary message
+    /// would interrupt a fragmented message that is in progress.
+    pub(super) fn start_send(&mut self, item: Message) -> Result<(), Error> {
+        if self.state != StreamState::Active {
+            return Err(Error::AlreadyClosed);
+        }
+
+        self.check_no_message_in_progress(item.opcode)?;
+
+        if item.opcode.is_control() || item.payload.len() <= self.config.frame_size {
+            let frame: Frame = item.into();
+            self.queue_frame(frame);
+        } else {
+            // Chunk the message into frames
+            for frame in item.into_frames(self.config.frame_size) {
+                self.queue_frame(frame);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queues a message that was encoded in advance for sending. Servers
+    /// without extensions queue the shared frames as they are, otherwise the
+    /// message is encoded again like by [`Self::start_send`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the connection was
+    /// closed or [`ProtocolError::InvalidOpcode`] if a text or binary message
+    /// would interrupt a fragmented message that is in progress.
+    pub(super) fn start_send_encoded(&mut self, item: &EncodedMessage) -> Result<(), Error> {
+        if self.state != StreamState::Active {
+            return Err(Error::AlreadyClosed);
+        }
+
+        self.check_no_message_in_progress(item.message.opcode)?;
+
+        // Close frames have to go through encode_frame to update the state
+        if self.role == Role::Client
+            || self.has_extensions()
+            || item.message.opcode == OpCode::Close
+        {
+            return self.start_send(item.message.clone());
+        }
+
+        let frame = EncodedFrame {
+            header: [0; 10],
+            header_len: 0,
+            mask: None,
+            payload: Payload::from(item.frames.clone()),
+            is_ping_reply: false,
+        };
+        self.pending_bytes += frame.len();
+        self.frame_queue.push_back(frame);
+
+        Ok(())
+    }
+
+    /// Checks that a data frame with `opcode` does not slip into a fragmented
+    /// message whose remaining frames are yet to be sent, for example by a
+    /// [`MessageWriter`](super::MessageWriter).
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`ProtocolError::InvalidOpcode`] if a fragmented
+    /// message is in progress and `opcode` is not a control opcode.
+    fn check_no_message_in_progress(&self, opcode: OpCode) -> Result<(), Error> {
+        if !opcode.is_control() && self.outgoing_message_opcode != OpCode::Continuation {
+            return Err(Error::Protocol(ProtocolError::InvalidOpcode));
+        }
+
+        Ok(())
+    }
+
+    /// Queues a frame that was created by the user for sending, after checking
+    /// it like the codec checks incoming frames: control frames must not be
+    /// fragmented or exceed 125 bytes, close frames must have a valid code and
+    /// reason, continuation frames must continue a fragmented message that no
+    /// other data frame may interrupt and text messages must be valid UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the connection was
+    /// closed or an [`Error::Protocol`] if the frame violates the protocol.
+    pub(super) fn start_send_checked_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        if self.state != StreamState::Active {
+            return Err(Error::AlreadyClosed);
+        }
+
+        if frame.opcode.is_control() {
+            if !frame.is_final {
+                return Err(Error::Protocol(ProtocolError::FragmentedControlFrame));
+            } else if frame.payload.len() > 125 {
+                return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
+            }
+
+            if frame.opcode == OpCode::Close {
+                match frame.payload.split_first_chunk::<2>() {
+                    Some((code, reason)) => {
+                        if !CloseCode::try_from(u16::from_be_bytes(*code))?.is_sendable() {
+                            return Err(Error::Protocol(ProtocolError::InvalidCloseCode));
+                        }
+
+                        utf8::parse_str(reason)?;
+                    }
+                    None if !frame.payload.is_empty() => {
+                        return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
+                    }
+                    None => {}
+                }
+            }
+        } else {
+            let message_opcode = if frame.opcode == OpCode::Continuation {
+                self.outgoing_message_opcode
+            } else if self.outgoing_message_opcode == OpCode::Continuation {
+                self.outgoing_validator.reset();
+                frame.opcode
+            } else {
+                OpCode::Continuation
+            };
+
+            // Either a continuation frame without a message in progress or a new
+            // message that interrupts the one in progress
+            if message_opcode == OpCode::Continuation {
+                return Err(Error::Protocol(ProtocolError::InvalidOpcode));
+            } else if message_opcode == OpCode::Text {
+                self.outgoing_validator
+                    .feed(&frame.payload, frame.is_final)?;
+            }
+        }
+
+        self.start_send_frame(frame)
+    }
+
+    /// Queues a frame for sending if the connection is still active and keeps
+    /// track of fragmented messages that are in progress.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the connection was
+    /// closed.
+    pub(super) fn start_send_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        if self.state != StreamState::Active {
+            return Err(Error::AlreadyClosed);
+        }
+
+        if !frame.opcode.is_control() {
+            if frame.is_final {
+                self.outgoing_message_opcode = OpCode::Continuation;
+            } else if frame.opcode != OpCode::Continuation {
+                self.outgoing_message_opcode = frame.opcode;
+            }
+        }
+
+        self.queue_frame(frame);
+
+        Ok(())
+    }
+
+    /// Encodes, masks and queues a frame for sending when [`Self::poll_flush`]
+    /// gets called.
+    pub(super) fn queue_frame(&mut self, frame: Frame) {
+        let frame = self.encode_frame(frame, false);
+        self.pending_bytes += frame.len();
+        self.frame_queue.push_back(frame);
+    }
+
+    /// Queues a pong in reply to a ping. Only the latest ping has to be
+    /// answered, so a reply that was queued earlier and has not started being
+    /// written yet is replaced instead of queueing another one. This keeps a
+    /// peer that floods pings from growing the queue.
+    fn queue_ping_reply(&mut self, frame: Frame) {
+        let frame = self.encode_frame(frame, true);
+        // The first frame in the queue may already be partially written
+        let skip = usize::from(self.bytes_written != 0);
+
+        if let Some(queued) = self
+            .frame_queue
+            .iter_mut()
+            .skip(skip)
+            .find(|queued| queued.is_ping_reply)
+        {
+            self.pending_bytes -= queued.len();
+            self.pending_bytes += frame.len();
+            *queued = frame;
+        } else {
+            self.pending_bytes += frame.len();
+            self.frame_queue.push_back(frame);
+        }
+    }
+
+    /// Applies the negotiated extensions to a frame, then encodes and masks it.
+    fn encode_frame(&mut self, frame: Frame, is_ping_reply: bool) -> EncodedFrame {
+        if frame.opcode == OpCode::Close && self.state != StreamState::ClosedByPeer {
+            self.set_closing(StreamState::ClosedByUs);
+        }
+
+        let frame = self.extensions.encode(frame);
+
+        let mask = if self.role == Role::Client {
+            #[cfg(feature = "client")]
+            {
+                Some(crate::rand::get_mask())
+            }
+            #[cfg(not(feature = "client"))]
+            {
+                // SAFETY: This allows for making the dependency on random generators
+                // only required for clients, servers can avoid it entirely.
+                // Since it is not possible to create a stream with client role
+                // without the client builder (and that is locked behind the client feature),
+                // this branch is impossible to reach.
+                unsafe { std::hint::unreachable_unchecked() }
+            }
+        } else {
+            None
+        };
+
+        let header_len = frame.encode(&mut self.header_buf);
+        if mask.is_some() {
+            self.header_buf[1] |= 1 << 7;
+        }
+
+        EncodedFrame {
+            header: self.header_buf,
+            header_len,
+            mask,
+            payload: frame.payload,
+            is_ping_reply,
+        }
+    }
+
+    /// Whether enough bytes are pending to flush the frame queue before
+    /// accepting more items.
+    pub(super) fn needs_flush(&self) -> bool {
+        // tokio-util calls poll_flush when more than 8096 bytes are pending, otherwise
+        // it returns Ready. We will just replicate that behavior
+        self.pending_bytes >= self.config.flush_threshold || self.is_over_pending_limit()
+    }
+
+    /// Whether more bytes are queued than [`Config::max_pending_bytes`]
+    /// allows, in which case no more frames are read until the queue was
+    /// flushed.
+    ///
+    /// [`Config::max_pending_bytes`]: super::Config::max_pending_bytes
+    pub(super) fn is_over_pending_limit(&self) -> bool {
+        self.config
+            .max_pending_bytes
+            .is_some_and(|max| self.pending_bytes > max)
+    }
+
+    /// Writes all queued frames to `io` and flushes it.
+    pub(super) fn poll_flush<W>(
+        &mut self,
+        io: &mut W,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        while !self.frame_queue.is_empty() {
+            // Gather as many queued frames as possible into a single write
+            let max_chunks = if io.is_write_vectored() {
+                MAX_IO_SLICES
+            } else {
+                1
+            };
+            let mut chunks = [Chunk::Unmasked(&[]); MAX_IO_SLICES];
+            let mut len = 0;
+            let mut skip = self.bytes_written;
+            self.mask_buf.clear();
+
+            'frames: for frame in &self.frame_queue {
+                for (index, part) in frame.parts().into_iter().enumerate() {
+                    if skip >= part.len() {
+                        skip -= part.len();
+                        continue;
+                    } else if len == max_chunks {
+                        break 'frames;
+                    }
+
+                    // SAFETY: skip < part.len()
+                    let part = unsafe { part.get_unchecked(skip..) };
+
+                    let (chunk, is_partial) = match frame.mask.filter(|_| index == 2) {
+                        // Payloads are masked while writing to avoid copying them up front
+                        Some(mask) => {
+                            let start = self.mask_buf.len();
+                            let n = part.len().min(MASK_BUF_SIZE - start);
+
+                            if n == 0 {
+                                break 'frames;
+                            }
+
+                            self.mask_buf.reserve_exact(MASK_BUF_SIZE - start);
+                            // SAFETY: n <= part.len()
+                            self.mask_buf
+                                .extend_from_slice(unsafe { part.get_unchecked(..n) });
+                            // SAFETY: start <= mask_buf.len()
+                            crate::mask::frame(
+                                mask,
+                                unsafe { self.mask_buf.get_unchecked_mut(start..) },
+                                skip & 3,
+                            );
+
+                            (Chunk::Masked(start, start + n), n < part.len())
+                        }
+                        None => (Chunk::Unmasked(part), false),
+                    };
+                    chunks[len] = chunk;
+                    skip = 0;
+                    len += 1;
+
+                    // The rest of a partially masked payload has to be written before
+                    // anything that follows it
+                    if is_partial {
+                        break 'frames;
+                    }
+                }
+            }
+
+            let n = if max_chunks == 1 {
+                let buf = chunks[0].get(&self.mask_buf);
+                ready!(Pin::new(&mut *io).poll_write(cx, buf))?
+            } else {
+                let mut slices = [IoSlice::new(&[]); MAX_IO_SLICES];
+                for (slice, chunk) in slices.iter_mut().zip(&chunks[..len]) {
+                    *slice = IoSlice::new(chunk.get(&self.mask_buf));
+                }
+
+                ready!(Pin::new(&mut *io).poll_write_vectored(cx, &slices[..len]))?
+            };
+
+            if n == 0 {
+                return Poll::Ready(Err(Error::Io(io::ErrorKind::WriteZero.into())));
+            }
+
+            self.pending_bytes -= n;
+            self.bytes_written += n;
+
+            // Remove all frames that were written completely
+            while let Some(frame) = self.frame_queue.front() {
+                if self.bytes_written < frame.len() {
+                    break;
+                }
+
+                self.bytes_written -= frame.len();
+                self.frame_queue.pop_front();
+            }
+        }
+
+        ready!(Pin::new(io).poll_flush(cx))?;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Replaces an empty read buffer whose capacity grew beyond `high_water_mark`
+/// with a new one of `capacity`, releasing the memory held by the old one.
+pub(super) fn shrink_read_buffer(
+    buf: &mut BytesMut,
+    capacity: usize,
+    high_water_mark: Option<usize>,
+) {
+    if buf.is_empty() && high_water_mark.is_some_and(|max| buf.capacity() > max) {
+        *buf = BytesMut::with_capacity(capacity);
+    }
+}
+
+/// A text or binary message that is being assembled from its frames.
+#[derive(Debug)]
+pub(super) struct PartialMessage {
+    /// Opcode of the full message that is being assembled.
+    opcode: OpCode,
+    /// Payload of the full message that is being assembled.
+    payload: BytesMut,
+}
+
+impl PartialMessage {
+    /// Creates an empty partial message.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(super) fn new() -> Self {
+        Self {
+            opcode: OpCode::Continuation,
+            payload: BytesMut::new(),
+        }
+    }
+
+    /// The length of the payload assembled so far.
+    pub(super) fn payload_len(&self) -> usize {
+        self.payload.len()
+    }
+
+    /// The capacity allocated for the payload assembled so far.
+    pub(super) fn payload_capacity(&self) -> usize {
+        self.payload.capacity()
+    }
+
+    /// Adds a frame to the message and returns the message once it is
+    /// complete. Unfragmented messages, including control frames, are returned
+    /// immediately.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::PayloadTooLong`] if the payload of the
+    /// message exceeds `max_len`.
+    pub(super) fn push(&mut self, frame: Frame, max_len: usize) -> Option<Result<Message, Error>> {
+        let Frame {
+            opcode,
+            is_final,
+            payload,
+            ..
+        } = frame;
+        let len = self.payload.len() + payload.len();
+
+        if opcode != OpCode::Continuation {
+            if is_final {
+                return Some(Ok(Message { opcode, payload }));
+            }
+            self.opcode = opcode;
+            self.payload = BytesMut::from(payload);
+        } else if len > max_len {
+            return Some(Err(Error::PayloadTooLong { len, max_len }));
+        } else {
+            self.payload.extend_from_slice(&payload);
+        }
+
+        if !is_final {
+            return None;
+        }
+
+        let opcode = replace(&mut self.opcode, OpCode::Continuation);
+        let mut payload = Payload::from(take(&mut self.payload));
+        payload.set_utf8_validated(opcode == OpCode::Text);
+
+        Some(Ok(Message { opcode, payload }))
+    }
+}
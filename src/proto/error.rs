@@ -10,12 +10,16 @@ pub enum ProtocolError {
     FragmentedControlFrame,
     /// An invalid close code has been received.
     InvalidCloseCode,
+    /// A compressed message could not be decompressed.
+    #[cfg(feature = "permessage-deflate")]
+    InvalidCompressedData,
     /// An invalid opcode was received.
     InvalidOpcode,
     /// An invalid payload length was received.
     InvalidPayloadLength,
-    /// An invalid RSV was received. This is used by extensions, which are
-    /// currently unsupported.
+    /// An invalid RSV was received. These bits are reserved for use by
+    /// extensions and may only be set if the extension using them was
+    /// negotiated.
     InvalidRsv,
     /// An invalid UTF-8 segment was received when valid UTF-8 was expected.
     InvalidUtf8,
@@ -31,6 +35,8 @@ impl ProtocolError {
         match self {
             ProtocolError::FragmentedControlFrame => "fragmented control frame",
             ProtocolError::InvalidCloseCode => "invalid close code",
+            #[cfg(feature = "permessage-deflate")]
+            ProtocolError::InvalidCompressedData => "invalid compressed data",
             ProtocolError::InvalidOpcode => "invalid opcode",
             ProtocolError::InvalidPayloadLength => "invalid payload length",
             ProtocolError::InvalidRsv => "invalid extension",
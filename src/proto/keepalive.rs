@@ -0,0 +1,124 @@
+//! Automatic pings that keep a connection alive, detect unresponsive peers and
+//! measure the round-trip time.
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::time::{sleep_until, Instant, Sleep};
+
+use super::types::{Config, Frame, OpCode, Payload, Rsv};
+
+/// An action that the [`Keepalive`] timer requests from the stream.
+pub(super) enum KeepaliveEvent {
+    /// A ping frame is due to be sent.
+    Ping(Frame),
+    /// The peer did not send anything in time after a ping.
+    Timeout,
+}
+
+/// Timer state for sending pings at a fixed interval and detecting missing
+/// replies.
+#[derive(Debug)]
+pub(super) struct Keepalive {
+    /// Interval at which pings are sent.
+    interval: Duration,
+    /// Time after a ping within which the peer has to send a frame.
+    timeout: Option<Duration>,
+    /// Timer for the next ping or timeout, created when first polled so that
+    /// streams can be created outside of a runtime.
+    sleep: Option<Pin<Box<Sleep>>>,
+    /// When the next ping is due.
+    next_ping: Instant,
+    /// When the peer has to have sent a frame, if a ping is unanswered.
+    deadline: Option<Instant>,
+    /// Payload and send time of the last unanswered ping.
+    last_ping: Option<([u8; 8], Instant)>,
+    /// Sequence number of the next ping, used as its payload.
+    sequence: u64,
+    /// The latest measured round-trip time.
+    rtt: Option<Duration>,
+}
+
+impl Keepalive {
+    /// Creates the keepalive timer for a stream if pings are enabled in the
+    /// config.
+    pub(super) fn new(config: &Config) -> Option<Self> {
+        config.ping_interval.map(|interval| Self {
+            interval,
+            timeout: config.pong_timeout,
+            sleep: None,
+            next_ping: Instant::now() + interval,
+            deadline: None,
+            last_ping: None,
+            sequence: 0,
+            rtt: None,
+        })
+    }
+
+    /// The latest round-trip time measured from a ping and its pong.
+    pub(super) fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// Records a frame received from the peer, which proves that the peer is
+    /// still alive.
+    pub(super) fn receive(&mut self, frame: &Frame) {
+        self.deadline = None;
+
+        if frame.opcode == OpCode::Pong {
+            if let Some((payload, sent_at)) = self.last_ping {
+                if *frame.payload == payload {
+                    self.rtt = Some(sent_at.elapsed());
+                    self.last_ping = None;
+                }
+            }
+        }
+    }
+
+    /// Waits for the next ping to be due or the peer to time out.
+    ///
+    /// The timer is only registered with the waker once this returns
+    /// [`Poll::Pending`], so callers must poll it until it does.
+    pub(super) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<KeepaliveEvent> {
+        let next = self
+            .deadline
+            .map_or(self.next_ping, |deadline| deadline.min(self.next_ping));
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(sleep_until(next)));
+
+        if sleep.deadline() != next {
+            sleep.as_mut().reset(next);
+        }
+
+        ready!(sleep.as_mut().poll(cx));
+
+        let now = Instant::now();
+
+        if self.deadline.is_some_and(|deadline| deadline <= now) {
+            self.deadline = None;
+
+            return Poll::Ready(KeepaliveEvent::Timeout);
+        }
+
+        let payload = self.sequence.to_be_bytes();
+        self.sequence = self.sequence.wrapping_add(1);
+        self.next_ping = now + self.interval;
+        self.last_ping = Some((payload, now));
+
+        if self.deadline.is_none() {
+            self.deadline = self.timeout.map(|timeout| now + timeout);
+        }
+
+        Poll::Ready(KeepaliveEvent::Ping(Frame {
+            opcode: OpCode::Ping,
+            is_final: true,
+            rsv: Rsv::NONE,
+            payload: Payload::from(Bytes::copy_from_slice(&payload)),
+        }))
+    }
+}
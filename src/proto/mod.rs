@@ -1,15 +1,28 @@
 //! This module contains a correct and complete implementation of [RFC6455](https://datatracker.ietf.org/doc/html/rfc6455).
 //!
-//! Any extensions are currently not implemented.
+//! Extensions are implemented in the [`extensions`] module.
+//!
+//! [`extensions`]: crate::extensions
 #[cfg(any(feature = "client", feature = "server"))]
 pub(crate) use self::types::Role;
 pub use self::{
     error::ProtocolError,
-    stream::WebSocketStream,
-    types::{CloseCode, Config, Limits, Message, Payload},
+    reader::MessageReader,
+    split::{ReadHalf, ReuniteError, WriteHalf},
+    stream::{Frames, WebSocketStream},
+    types::{
+        CloseCode, CloseFrame, Config, EncodedMessage, Frame, Limits, Message, MessageKind,
+        MessageRef, OpCode, Payload, Rsv, StreamState, Utf8Payload,
+    },
+    writer::MessageWriter,
 };
 
 mod codec;
+mod connection;
 mod error;
+mod keepalive;
+mod reader;
+mod split;
 mod stream;
 mod types;
+mod writer;
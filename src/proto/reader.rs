@@ -0,0 +1,122 @@
+//! Streaming reader for the payload of a single message.
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::{
+    stream::WebSocketStream,
+    types::{Frame, OpCode},
+};
+use crate::Error;
+
+/// A reader for the payload of a single text or binary message that reads the
+/// frames of the message as they arrive, created by
+/// [`WebSocketStream::next_message_reader`].
+///
+/// The reader implements [`AsyncRead`]. The payload of text messages is
+/// validated to be UTF-8 as it arrives and [`Limits`] apply to the full
+/// message. Errors are reported as [`io::Error`]s that wrap the [`Error`]
+/// encountered.
+///
+/// Dropping the reader before the end of the message discards the rest of the
+/// message.
+///
+/// [`Limits`]: crate::Limits
+#[derive(Debug)]
+pub struct MessageReader<'a, T> {
+    /// The stream the message is read from.
+    stream: &'a mut WebSocketStream<T>,
+    /// The opcode of the message.
+    opcode: OpCode,
+    /// The unread payload of the current frame.
+    chunk: Bytes,
+    /// Total payload length of the frames received so far.
+    len: usize,
+    /// Whether the last frame of the message was received.
+    is_final: bool,
+}
+
+impl<'a, T> MessageReader<'a, T> {
+    /// Creates a new reader from the first frame of a message.
+    pub(super) fn new(stream: &'a mut WebSocketStream<T>, frame: Frame) -> Self {
+        Self {
+            stream,
+            opcode: frame.opcode,
+            len: frame.payload.len(),
+            is_final: frame.is_final,
+            chunk: frame.payload.into(),
+        }
+    }
+
+    /// Returns whether the message is a text message.
+    #[must_use]
+    pub fn is_text(&self) -> bool {
+        self.opcode == OpCode::Text
+    }
+
+    /// Returns whether the message is a binary message.
+    #[must_use]
+    pub fn is_binary(&self) -> bool {
+        self.opcode == OpCode::Binary
+    }
+}
+
+impl<T> AsyncRead for MessageReader<'_, T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+
+        loop {
+            if !this.chunk.is_empty() {
+                let n = this.chunk.len().min(buf.remaining());
+                buf.put_slice(&this.chunk[..n]);
+                this.chunk.advance(n);
+
+                return Poll::Ready(Ok(()));
+            } else if this.is_final {
+                return Poll::Ready(Ok(()));
+            }
+
+            match ready!(Pin::new(&mut *this.stream).poll_next_frame(cx)) {
+                Some(Ok(frame)) if frame.opcode == OpCode::Continuation => {
+                    let max_len = this.stream.max_message_len();
+                    this.len += frame.payload.len();
+
+                    if this.len > max_len {
+                        return Poll::Ready(Err(Error::PayloadTooLong {
+                            len: this.len,
+                            max_len,
+                        }
+                        .into_io_error()));
+                    }
+
+                    this.is_final = frame.is_final;
+                    this.chunk = frame.payload.into();
+                }
+                // Control frames are handled by the stream
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Poll::Ready(Err(e.into_io_error())),
+                None => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+            }
+        }
+    }
+}
+
+impl<T> Drop for MessageReader<'_, T> {
+    fn drop(&mut self) {
+        if !self.is_final {
+            self.stream.discard_message();
+        }
+    }
+}
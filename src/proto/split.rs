@@ -0,0 +1,638 @@
+//! Independently owned read and write halves of a [`WebSocketStream`].
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::poll_fn,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{ready, Context, Poll, Waker},
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::{
+    io::{self, AsyncRead, AsyncWrite},
+    time::Sleep,
+};
+use tokio_util::codec::{Decoder, FramedRead};
+
+use super::{
+    codec::WebSocketProtocol,
+    connection::{shrink_read_buffer, Connection, PartialMessage},
+    keepalive::Keepalive,
+    stream::WebSocketStream,
+    types::{CloseFrame, Config, EncodedMessage, Frame, Message, OpCode, StreamState},
+    writer::{MessageWriter, Target},
+};
+use crate::Error;
+
+/// State shared by the halves of a split stream.
+#[derive(Debug)]
+struct Shared<T> {
+    /// The protocol state of the connection and queue of outgoing frames.
+    connection: Connection,
+    /// The writing half of the underlying I/O.
+    io: io::WriteHalf<T>,
+    /// Waker of the read half if it is waiting for a flush to complete.
+    read_waker: Option<Waker>,
+    /// Waker of the write half if it is waiting for a flush to complete.
+    write_waker: Option<Waker>,
+}
+
+impl<T> Shared<T> {
+    /// Wakes the read half if the connection started closing, since it has to
+    /// start waiting for the close timeout.
+    fn wake_read_half_if_closing(&mut self) {
+        if self.connection.state != StreamState::Active {
+            if let Some(waker) = self.read_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite> Shared<T> {
+    /// Writes all queued frames on behalf of the read half if `read` is set,
+    /// otherwise on behalf of the write half.
+    ///
+    /// Only the task that polled the I/O last is woken by it, so the half that
+    /// completes a flush wakes the other half if it is still waiting for one.
+    fn poll_flush(&mut self, cx: &mut Context<'_>, read: bool) -> Poll<Result<(), Error>> {
+        let poll = self.connection.poll_flush(&mut self.io, cx);
+
+        let (own, other) = if read {
+            (&mut self.read_waker, &mut self.write_waker)
+        } else {
+            (&mut self.write_waker, &mut self.read_waker)
+        };
+
+        if poll.is_pending() {
+            *own = Some(cx.waker().clone());
+        } else {
+            *own = None;
+
+            if let Some(waker) = other.take() {
+                waker.wake();
+            }
+        }
+
+        poll
+    }
+
+    /// Checks whether the close handshake timed out using the `timer` of the
+    /// polling half, in which case the writing half of the underlying I/O is
+    /// shut down without waiting for it to complete.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::CloseTimeout`] if the close handshake
+    /// timed out.
+    fn check_close_timeout(
+        &mut self,
+        timer: &mut Option<Pin<Box<Sleep>>>,
+        cx: &mut Context<'_>,
+    ) -> Result<(), Error> {
+        if self.connection.poll_close_timeout(timer, cx).is_ready() {
+            _ = Pin::new(&mut self.io).poll_shutdown(cx);
+            return Err(Error::CloseTimeout);
+        }
+
+        Ok(())
+    }
+}
+
+/// Locks the state shared by the halves.
+fn lock<T>(shared: &Mutex<Shared<T>>) -> MutexGuard<'_, Shared<T>> {
+    // The frame queue is only modified once a frame is fully encoded, a panic
+    // while the lock is held cannot leave it in an inconsistent state
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Creates a [`FramedRead`] over `io` that continues with the decoder and read
+/// buffer of a previous one.
+///
+/// A new [`FramedRead`] only decodes buffered data after more data was read
+/// from `io`, so frames that are already complete in the buffer are decoded
+/// into `decoded` right away.
+fn reframe<R>(
+    mut codec: WebSocketProtocol,
+    mut read_buf: BytesMut,
+    io: R,
+    decoded: &mut VecDeque<Result<Frame, Error>>,
+) -> FramedRead<R, WebSocketProtocol> {
+    loop {
+        match codec.decode(&mut read_buf) {
+            Ok(Some(frame)) => decoded.push_back(Ok(frame)),
+            Ok(None) => break,
+            Err(e) => {
+                decoded.push_back(Err(e));
+                break;
+            }
+        }
+    }
+
+    let mut framed = FramedRead::new(io, codec);
+    *framed.read_buffer_mut() = read_buf;
+
+    framed
+}
+
+impl<T> WebSocketStream<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Splits the stream into a [`ReadHalf`] and a [`WriteHalf`] that can be
+    /// used from different tasks.
+    ///
+    /// Unlike splitting the stream with a lock around it, neither half holds a
+    /// lock while it waits for the underlying I/O, so a read that waits for
+    /// data does not block writing and vice versa. Both halves still lock
+    /// briefly on every poll, though:
+    ///
+    /// - The I/O is split with [`tokio::io::split`], which guards it with a
+    ///   lock of its own.
+    /// - The write half locks the state it shares with the read half to queue
+    ///   and flush frames. The read half only locks it to queue replies to
+    ///   pings and close frames, or when negotiated extensions transform
+    ///   incoming frames.
+    ///
+    /// The halves can be put back together with [`ReadHalf::reunite`].
+    pub fn split(self) -> (ReadHalf<T>, WriteHalf<T>) {
+        let WebSocketStream {
+            inner,
+            connection,
+            partial,
+            discard_message,
+            mut decoded,
+            keepalive,
+            close_timer,
+        } = self;
+
+        let parts = inner.into_parts();
+        let (read, write) = io::split(parts.io);
+        let inner = reframe(parts.codec, parts.read_buf, read, &mut decoded);
+
+        let read = ReadHalf {
+            inner,
+            has_extensions: connection.has_extensions(),
+            config: connection.config,
+            state: connection.state,
+            flush_pending: connection.has_pending_frames(),
+            partial,
+            discard_message,
+            decoded,
+            keepalive,
+            close_timer,
+            shared: Arc::new(Mutex::new(Shared {
+                connection,
+                io: write,
+                read_waker: None,
+                write_waker: None,
+            })),
+        };
+        let write = WriteHalf {
+            shared: read.shared.clone(),
+            close_timer: None,
+        };
+
+        (read, write)
+    }
+}
+
+/// The read half of a [`WebSocketStream`], created by
+/// [`WebSocketStream::split`].
+///
+/// The read half implements [`futures_core::Stream`]. Pings and close frames
+/// are still answered automatically: the replies are queued for the
+/// [`WriteHalf`] and sent whenever either half flushes, which the read half
+/// attempts before reading further.
+#[derive(Debug)]
+pub struct ReadHalf<T> {
+    /// The reading half of the underlying I/O using the [`WebSocketProtocol`]
+    /// to read full frames.
+    inner: FramedRead<io::ReadHalf<T>, WebSocketProtocol>,
+    /// State shared with the write half.
+    shared: Arc<Mutex<Shared<T>>>,
+    /// Whether extensions were negotiated that have to decode incoming frames.
+    has_extensions: bool,
+    /// Configuration of the stream that applies to reading, such as the
+    /// timeout of the close handshake, which this half has to observe even if
+    /// the write half started closing.
+    config: Config,
+    /// The [`StreamState`] of the connection as last seen by this half.
+    state: StreamState,
+    /// Whether this half queued replies that may not have been written yet.
+    flush_pending: bool,
+    /// The full message that is being assembled.
+    partial: PartialMessage,
+    /// Whether the remaining frames of a partially read message are to be
+    /// discarded.
+    discard_message: bool,
+    /// Frames that were decoded ahead of time when the stream was split.
+    decoded: VecDeque<Result<Frame, Error>>,
+    /// Timer for automatic pings, if enabled.
+    keepalive: Option<Keepalive>,
+    /// Timer of this half for the timeout of the close handshake, once it
+    /// started.
+    close_timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> ReadHalf<T>
+where
+    T: AsyncRead + AsyncWrite,
+{
+    /// Attempt to pull out the next frame from the [`FramedRead`] and from
+    /// that update the shared state of the connection.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if reading from the stream fails or a
+    /// protocol violation is encountered.
+    fn poll_read_frame(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Frame, Error>>> {
+        // In the case of ClosedByPeer, we have to flush to make sure our close
+        // acknowledge goes through.
+        if matches!(
+            self.state,
+            StreamState::CloseAcknowledged | StreamState::Terminated
+        ) {
+            return Poll::Ready(None);
+        } else if self.state != StreamState::Active {
+            let mut shared = lock(&self.shared);
+            let timeout = shared.check_close_timeout(&mut self.close_timer, cx);
+            self.state = shared.connection.state;
+            timeout?;
+
+            if self.state == StreamState::ClosedByPeer {
+                ready!(shared.poll_flush(cx, true))?;
+                shared.connection.state = StreamState::CloseAcknowledged;
+                self.state = StreamState::CloseAcknowledged;
+                return Poll::Ready(None);
+            }
+        }
+
+        // Send pings that are due and detect an unresponsive peer
+        if let Some(keepalive) = &mut self.keepalive {
+            while let Poll::Ready(event) = keepalive.poll(cx) {
+                let mut shared = lock(&self.shared);
+                let result = shared.connection.keepalive(event);
+                self.flush_pending |= shared.connection.has_pending_frames();
+                self.state = shared.connection.state;
+
+                if let Err(e) = result {
+                    _ = shared.poll_flush(cx, true)?;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+
+        // If replies were queued, try to flush them. Too many queued frames
+        // have to be written before reading on
+        if self.flush_pending {
+            let mut shared = lock(&self.shared);
+            let poll = shared.poll_flush(cx, true);
+
+            if let Poll::Ready(result) = poll {
+                self.flush_pending = false;
+                result?;
+            } else if shared.connection.is_over_pending_limit() {
+                return Poll::Pending;
+            }
+        }
+
+        let frame = if let Some(frame) = self.decoded.pop_front() {
+            frame
+        } else {
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(frame)) => frame,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => {
+                    // Release memory of large frames while waiting for the peer
+                    shrink_read_buffer(
+                        self.inner.read_buffer_mut(),
+                        self.config.read_buffer_capacity,
+                        self.config.read_buffer_high_water_mark,
+                    );
+
+                    // The write half may start closing while this half waits
+                    // for data, after which it has to wait for the timeout too
+                    if self.config.close_timeout.is_some() && self.state == StreamState::Active {
+                        let mut shared = lock(&self.shared);
+                        self.state = shared.connection.state;
+
+                        if self.state == StreamState::Active {
+                            shared.read_waker = Some(cx.waker().clone());
+                        } else {
+                            shared.check_close_timeout(&mut self.close_timer, cx)?;
+                        }
+                    }
+
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        if let (Some(keepalive), Ok(frame)) = (&mut self.keepalive, &frame) {
+            keepalive.receive(frame);
+        }
+
+        // Data frames only need the shared state if extensions decode them
+        let frame = match frame {
+            Ok(frame) if !self.has_extensions && !frame.opcode.is_control() => {
+                return Poll::Ready(Some(Ok(frame)));
+            }
+            frame => frame,
+        };
+
+        // Decoded payloads of messages may not exceed the limit either
+        let max_len = self
+            .inner
+            .decoder()
+            .max_message_len()
+            .saturating_sub(self.partial.payload_len());
+
+        let mut shared = lock(&self.shared);
+        self.state = shared.connection.state;
+
+        // The write half may have completed the close handshake or terminated the
+        // connection since the state was last read, e.g. when the close timeout expired
+        if matches!(
+            self.state,
+            StreamState::CloseAcknowledged | StreamState::Terminated
+        ) {
+            return Poll::Ready(None);
+        }
+
+        let frame = shared.connection.receive(frame, max_len);
+        self.flush_pending |= shared.connection.has_pending_frames();
+        self.state = shared.connection.state;
+
+        Poll::Ready(Some(frame))
+    }
+}
+
+impl<T> ReadHalf<T> {
+    /// Returns the latest round-trip time measured from an automatic ping and
+    /// its pong, see [`WebSocketStream::rtt`].
+    pub fn rtt(&self) -> Option<Duration> {
+        self.keepalive.as_ref().and_then(Keepalive::rtt)
+    }
+
+    /// Returns the close frame sent by the peer once it closed the connection,
+    /// see [`WebSocketStream::peer_close_frame`].
+    #[must_use]
+    pub fn peer_close_frame(&self) -> Option<CloseFrame> {
+        lock(&self.shared).connection.peer_close_frame().cloned()
+    }
+}
+
+impl<T> ReadHalf<T>
+where
+    T: Unpin,
+{
+    /// Reunites this half with the [`WriteHalf`] it was split from into the
+    /// original [`WebSocketStream`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns a [`ReuniteError`] containing both halves if they
+    /// were not split from the same stream.
+    #[allow(clippy::result_large_err)]
+    pub fn reunite(self, other: WriteHalf<T>) -> Result<WebSocketStream<T>, ReuniteError<T>> {
+        if !Arc::ptr_eq(&self.shared, &other.shared) {
+            return Err(ReuniteError(self, other));
+        }
+
+        drop(other);
+
+        let ReadHalf {
+            inner,
+            shared,
+            partial,
+            discard_message,
+            mut decoded,
+            keepalive,
+            close_timer,
+            ..
+        } = self;
+
+        let Some(shared) = Arc::into_inner(shared) else {
+            // Both halves were owned by the caller
+            unreachable!()
+        };
+        let Shared {
+            connection,
+            io: write,
+            ..
+        } = shared.into_inner().unwrap_or_else(PoisonError::into_inner);
+
+        let parts = inner.into_parts();
+        let inner = reframe(
+            parts.codec,
+            parts.read_buf,
+            parts.io.unsplit(write),
+            &mut decoded,
+        );
+
+        Ok(WebSocketStream {
+            inner,
+            connection,
+            partial,
+            discard_message,
+            decoded,
+            keepalive,
+            close_timer,
+        })
+    }
+}
+
+impl<T> Stream for ReadHalf<T>
+where
+    T: AsyncRead + AsyncWrite,
+{
+    type Item = Result<Message, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let Some(frame) = ready!(self.poll_read_frame(cx)?) else {
+                return Poll::Ready(None);
+            };
+
+            if self.discard_message && frame.opcode == OpCode::Continuation {
+                self.discard_message = !frame.is_final;
+                continue;
+            }
+
+            if !self.config.yield_pings_and_pongs
+                && matches!(frame.opcode, OpCode::Ping | OpCode::Pong)
+            {
+                continue;
+            }
+
+            let max_len = self.inner.decoder().max_message_len();
+
+            if let Some(message) = self.partial.push(frame, max_len) {
+                return Poll::Ready(Some(message));
+            }
+        }
+    }
+}
+
+/// The write half of a [`WebSocketStream`], created by
+/// [`WebSocketStream::split`].
+///
+/// The write half implements [`futures_sink::Sink`]. Closing it sends a close
+/// frame and shuts down the writing side of the underlying I/O. The close
+/// frame sent by the peer in response is received by the [`ReadHalf`].
+#[derive(Debug)]
+pub struct WriteHalf<T> {
+    /// State shared with the read half.
+    shared: Arc<Mutex<Shared<T>>>,
+    /// Timer of this half for the timeout of the close handshake, once it
+    /// started.
+    close_timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> WriteHalf<T> {
+    /// The maximum size of outgoing frames.
+    pub(super) fn frame_size(&self) -> usize {
+        lock(&self.shared).connection.config.frame_size
+    }
+
+    /// Queues a frame for sending if the connection is still active.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the connection was
+    /// closed.
+    pub(super) fn start_send_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        let mut shared = lock(&self.shared);
+        let result = shared.connection.start_send_frame(frame);
+        shared.wake_read_half_if_closing();
+
+        result
+    }
+}
+
+impl<T> WriteHalf<T>
+where
+    T: AsyncWrite,
+{
+    /// Starts sending a text or binary message whose payload is written
+    /// incrementally through the returned [`MessageWriter`], see
+    /// [`WebSocketStream::send_streaming`]. The [`ReadHalf`] keeps reading
+    /// meanwhile and its replies to pings are sent in between the frames of
+    /// the message.
+    ///
+    /// # Panics
+    ///
+    /// If `opcode` is neither [`OpCode::Text`] nor [`OpCode::Binary`].
+    pub fn send_streaming(&mut self, opcode: OpCode) -> MessageWriter<'_, T> {
+        assert!(
+            matches!(opcode, OpCode::Text | OpCode::Binary),
+            "streamed messages must be text or binary"
+        );
+
+        MessageWriter::new(Target::WriteHalf(self), opcode)
+    }
+
+    /// Closes the connection with a close frame of the given code and reason,
+    /// see [`WebSocketStream::close_with`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if writing to the stream fails or
+    /// flushing the close frame times out.
+    pub async fn close_with(&mut self, frame: CloseFrame) -> Result<(), Error> {
+        {
+            let mut shared = lock(&self.shared);
+
+            if shared.connection.state == StreamState::Active {
+                shared.connection.queue_frame(Message::from(frame).into());
+                shared.wake_read_half_if_closing();
+            }
+        }
+
+        poll_fn(|cx| Sink::<Message>::poll_close(Pin::new(&mut *self), cx)).await
+    }
+
+    /// Sends a message that was encoded in advance by [`Message::prepare`]
+    /// and flushes the stream, see [`WebSocketStream::send_encoded`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if writing to the stream fails or the
+    /// connection was closed.
+    pub async fn send_encoded(&mut self, message: &EncodedMessage) -> Result<(), Error> {
+        poll_fn(|cx| Sink::<Message>::poll_ready(Pin::new(&mut *self), cx)).await?;
+        {
+            let mut shared = lock(&self.shared);
+            let result = shared.connection.start_send_encoded(message);
+            shared.wake_read_half_if_closing();
+            result?;
+        }
+        poll_fn(|cx| Sink::<Message>::poll_flush(Pin::new(&mut *self), cx)).await
+    }
+}
+
+impl<T> Sink<Message> for WriteHalf<T>
+where
+    T: AsyncWrite,
+{
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut shared = lock(&self.shared);
+
+        if shared.connection.needs_flush() {
+            shared.poll_flush(cx, false)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        let mut shared = lock(&self.shared);
+        let result = shared.connection.start_send(item);
+        shared.wake_read_half_if_closing();
+
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        lock(&self.shared).poll_flush(cx, false)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        let mut shared = lock(&this.shared);
+
+        if shared.connection.state == StreamState::Active {
+            shared.connection.queue_frame(Frame::DEFAULT_CLOSE);
+            shared.wake_read_half_if_closing();
+        }
+
+        if shared.poll_flush(cx, false)?.is_pending() {
+            shared.check_close_timeout(&mut this.close_timer, cx)?;
+            return Poll::Pending;
+        }
+
+        Pin::new(&mut shared.io)
+            .poll_shutdown(cx)
+            .map_err(Error::Io)
+    }
+}
+
+/// Error returned by [`ReadHalf::reunite`] if the halves were not split from
+/// the same stream, containing both halves.
+#[derive(Debug)]
+pub struct ReuniteError<T>(pub ReadHalf<T>, pub WriteHalf<T>);
+
+impl<T> fmt::Display for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite halves that are not from the same stream")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for ReuniteError<T> {}
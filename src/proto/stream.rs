@@ -1,47 +1,41 @@
 //! Frame aggregating abstraction over the low-level [`super::codec`]
 //! implementation that provides [`futures_sink::Sink`] and
 //! [`futures_core::Stream`] implementations that take [`Message`] as a
-//! parameter.
+//! parameter, as well as [`Frames`] for working with individual frames.
 use std::{
     collections::VecDeque,
-    hint::unreachable_unchecked,
-    io,
-    mem::{replace, take},
+    future::poll_fn,
     pin::Pin,
     task::{ready, Context, Poll},
+    time::Duration,
 };
 
-use bytes::{Buf, BytesMut};
 use futures_core::Stream;
 use futures_sink::Sink;
-use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_util::{codec::FramedRead, io::poll_write_buf};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    time::Sleep,
+};
+use tokio_util::codec::FramedRead;
 
 #[cfg(any(feature = "client", feature = "server"))]
-use super::types::Limits;
+use super::types::Role;
 use super::{
     codec::WebSocketProtocol,
-    types::{Frame, Message, OpCode, Payload, Role, StreamState},
-    Config,
+    connection::{shrink_read_buffer, Connection, PartialMessage},
+    keepalive::Keepalive,
+    reader::MessageReader,
+    types::{CloseFrame, Config, EncodedMessage, Frame, Limits, Message, OpCode, StreamState},
+    writer::{MessageWriter, Target},
 };
-use crate::{CloseCode, Error};
-
-/// Helper struct for storing a frame header, the header size and payload.
-#[derive(Debug)]
-struct EncodedFrame {
-    /// Encoded frame header.
-    header: [u8; 10],
-    /// Length of the header.
-    header_len: u8,
-    /// Mask that the payload was masked with.
-    mask: Option<[u8; 4]>,
-    /// Potentially masked message payload, ready for writing to the I/O.
-    payload: Payload,
-}
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::extensions::Extensions;
+use crate::Error;
 
 /// A WebSocket stream that full messages can be read from and written to.
 ///
 /// The stream implements [`futures_sink::Sink`] and [`futures_core::Stream`].
+/// Individual frames can be read and written via [`WebSocketStream::frames`].
 ///
 /// You must use a [`ClientBuilder`] or [`ServerBuilder`] to
 /// obtain a WebSocket stream.
@@ -56,36 +50,26 @@ struct EncodedFrame {
 pub struct WebSocketStream<T> {
     /// The underlying stream using the [`WebSocketProtocol`] to read and write
     /// full frames.
-    inner: FramedRead<T, WebSocketProtocol>,
-
-    /// Configuration for the stream.
-    config: Config,
-
-    /// The [`StreamState`] of the current stream.
-    state: StreamState,
-
-    /// Payload of the full message that is being assembled.
-    partial_payload: BytesMut,
-    /// Opcode of the full message that is being assembled.
-    partial_opcode: OpCode,
-
-    /// Buffer that outgoing frame headers are formatted into.
-    header_buf: [u8; 10],
-
-    /// Queue of outgoing frames to send.
-    frame_queue: VecDeque<EncodedFrame>,
-    /// Amount of partial bytes written of the first frame in the queue.
-    bytes_written: usize,
-    /// Total amount of bytes remaining to be sent in the frame queue.
-    pending_bytes: usize,
+    pub(super) inner: FramedRead<T, WebSocketProtocol>,
+
+    /// The protocol state of the connection and queue of outgoing frames.
+    pub(super) connection: Connection,
+
+    /// The full message that is being assembled.
+    pub(super) partial: PartialMessage,
+    /// Whether the remaining frames of a partially read message are to be
+    /// discarded.
+    pub(super) discard_message: bool,
+    /// Frames that were decoded ahead of time when the halves of the stream
+    /// were reunited.
+    pub(super) decoded: VecDeque<Result<Frame, Error>>,
+
+    /// Timer for automatic pings, if enabled.
+    pub(super) keepalive: Option<Keepalive>,
+    /// Timer for the timeout of the close handshake, once it started.
+    pub(super) close_timer: Option<Pin<Box<Sleep>>>,
 }
 
-// SAFETY: The only !Sync field in `WebSocketStream` is `frame_queue`.
-// `frame_queue` must be used with exclusive, mutable access, which is
-// currently the case. It is only used in methods that take `&mut self`
-// and not borrowed in the methods.
-unsafe impl<T> Sync for WebSocketStream<T> {}
-
 impl<T> WebSocketStream<T>
 where
     T: AsyncRead + AsyncWrite + Unpin,
@@ -93,16 +77,20 @@ where
     /// Create a new [`WebSocketStream`] from a raw stream.
     #[cfg(any(feature = "client", feature = "server"))]
     pub(crate) fn from_raw_stream(stream: T, role: Role, config: Config, limits: Limits) -> Self {
+        let extensions = Extensions::default();
+
         Self {
-            inner: FramedRead::new(stream, WebSocketProtocol::new(role, limits)),
-            config,
-            state: StreamState::Active,
-            partial_payload: BytesMut::new(),
-            partial_opcode: OpCode::Continuation,
-            header_buf: [0; 10],
-            frame_queue: VecDeque::with_capacity(1),
-            bytes_written: 0,
-            pending_bytes: 0,
+            inner: FramedRead::with_capacity(
+                stream,
+                WebSocketProtocol::new(role, limits, extensions.rsv()),
+                config.read_buffer_capacity,
+            ),
+            connection: Connection::new(role, config, extensions, None),
+            partial: PartialMessage::new(),
+            discard_message: false,
+            decoded: VecDeque::new(),
+            keepalive: Keepalive::new(&config),
+            close_timer: None,
         }
     }
 
@@ -114,17 +102,29 @@ where
         role: Role,
         config: Config,
         limits: Limits,
+        extensions: Extensions,
+        subprotocol: Option<String>,
     ) -> Self {
+        let allowed_rsv = extensions.rsv();
+        let mut inner = framed.map_decoder(|_| WebSocketProtocol::new(role, limits, allowed_rsv));
+
+        // The buffer of the handshake is reused, but sized like a new one
+        let read_buf = inner.read_buffer_mut();
+        shrink_read_buffer(
+            read_buf,
+            config.read_buffer_capacity,
+            Some(config.read_buffer_capacity),
+        );
+        read_buf.reserve(config.read_buffer_capacity);
+
         Self {
-            inner: framed.map_decoder(|_| WebSocketProtocol::new(role, limits)),
-            config,
-            state: StreamState::Active,
-            partial_payload: BytesMut::new(),
-            partial_opcode: OpCode::Continuation,
-            header_buf: [0; 10],
-            frame_queue: VecDeque::with_capacity(1),
-            bytes_written: 0,
-            pending_bytes: 0,
+            inner,
+            connection: Connection::new(role, config, extensions, subprotocol),
+            partial: PartialMessage::new(),
+            discard_message: false,
+            decoded: VecDeque::new(),
+            keepalive: Keepalive::new(&config),
+            close_timer: None,
         }
     }
 
@@ -145,6 +145,156 @@ where
         self.inner.get_mut()
     }
 
+    /// Returns a [`Frames`] adapter that reads and writes individual frames
+    /// instead of full messages.
+    ///
+    /// All protocol checks of the stream still apply to frames read from the
+    /// adapter, including UTF-8 validation of text frames, and pings and close
+    /// frames are still answered automatically as configured in the
+    /// [`Config`]. [`Limits`] apply to single frames rather than full messages.
+    ///
+    /// The adapter does not assemble or split messages. Frames written to it
+    /// are sent as is, after the same checks that incoming frames go through:
+    /// control frames must be final and carry at most 125 bytes, continuation
+    /// frames must continue a fragmented message and text messages must be
+    /// valid UTF-8. Frames that violate the protocol are rejected with an
+    /// [`Error::Protocol`], as are text and binary messages sent while a
+    /// fragmented message is in progress. Switching between reading messages
+    /// and frames while a fragmented message is in progress corrupts the
+    /// message.
+    pub fn frames(&mut self) -> Frames<'_, T> {
+        Frames { stream: self }
+    }
+
+    /// Waits for the next text or binary message and returns a
+    /// [`MessageReader`] that reads its payload as the frames of the message
+    /// arrive, rather than buffering the full message in memory. Ping, pong
+    /// and close frames received in the meantime are skipped and handled
+    /// automatically.
+    ///
+    /// Returns `None` once the stream is closed.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if reading from the stream fails or a
+    /// protocol violation is encountered.
+    pub async fn next_message_reader(&mut self) -> Option<Result<MessageReader<'_, T>, Error>> {
+        loop {
+            let frame = match poll_fn(|cx| Pin::new(&mut *self).poll_next_frame(cx)).await? {
+                Ok(frame) => frame,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if !frame.opcode.is_control() {
+                return Some(Ok(MessageReader::new(self, frame)));
+            }
+        }
+    }
+
+    /// Starts sending a text or binary message whose payload is written
+    /// incrementally through the returned [`MessageWriter`], rather than being
+    /// buffered in full before sending.
+    ///
+    /// The writer borrows the stream, so no frames are read until it is
+    /// dropped and pings of the peer are not answered while a message is being
+    /// written. To keep answering them, [`split`] the stream and write the
+    /// message with [`WriteHalf::send_streaming`] instead.
+    ///
+    /// [`split`]: Self::split
+    /// [`WriteHalf::send_streaming`]: super::WriteHalf::send_streaming
+    ///
+    /// # Panics
+    ///
+    /// If `opcode` is neither [`OpCode::Text`] nor [`OpCode::Binary`].
+    pub fn send_streaming(&mut self, opcode: OpCode) -> MessageWriter<'_, T> {
+        assert!(
+            matches!(opcode, OpCode::Text | OpCode::Binary),
+            "streamed messages must be text or binary"
+        );
+
+        MessageWriter::new(Target::Stream(self), opcode)
+    }
+
+    /// Closes the connection with a close frame of the given code and reason
+    /// instead of [`CloseCode::NORMAL_CLOSURE`] and waits for the close
+    /// handshake to complete, like closing the stream as a
+    /// [`futures_sink::Sink`] does. If the connection is already closing, no
+    /// further close frame is sent.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if writing to the stream fails or the
+    /// close handshake times out.
+    ///
+    /// [`CloseCode::NORMAL_CLOSURE`]: crate::CloseCode::NORMAL_CLOSURE
+    pub async fn close_with(&mut self, frame: CloseFrame) -> Result<(), Error> {
+        if self.connection.state == StreamState::Active {
+            self.connection.queue_frame(Message::from(frame).into());
+        }
+
+        poll_fn(|cx| Sink::<Message>::poll_close(Pin::new(&mut *self), cx)).await
+    }
+
+    /// Sends a message that was encoded in advance by [`Message::prepare`]
+    /// and flushes the stream, like sending a [`Message`] as a
+    /// [`futures_sink::Sink`] does. Servers without negotiated extensions send
+    /// the shared frames of the message without encoding them again.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if writing to the stream fails or the
+    /// connection was closed.
+    pub async fn send_encoded(&mut self, message: &EncodedMessage) -> Result<(), Error> {
+        poll_fn(|cx| Sink::<Message>::poll_ready(Pin::new(&mut *self), cx)).await?;
+        self.connection.start_send_encoded(message)?;
+        poll_fn(|cx| Sink::<Message>::poll_flush(Pin::new(&mut *self), cx)).await
+    }
+
+    /// Attempt to pull out the next frame from the [`Framed`] this stream and
+    /// from that update the stream's internal state, skipping frames of
+    /// discarded messages and pings and pongs if they are not to be yielded.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if reading from the stream fails or a
+    /// protocol violation is encountered.
+    pub(super) fn poll_next_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame, Error>>> {
+        loop {
+            match ready!(self.as_mut().poll_read_frame(cx)?) {
+                Some(frame) if self.discard_message && frame.opcode == OpCode::Continuation => {
+                    self.discard_message = !frame.is_final;
+                }
+                Some(frame)
+                    if !self.connection.config.yield_pings_and_pongs
+                        && matches!(frame.opcode, OpCode::Ping | OpCode::Pong) => {}
+                frame => return Poll::Ready(frame.map(Ok)),
+            }
+        }
+    }
+
+    /// Checks whether the close handshake timed out, in which case the
+    /// underlying I/O is shut down without waiting for it to complete.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::CloseTimeout`] if the close handshake
+    /// timed out.
+    fn check_close_timeout(&mut self, cx: &mut Context<'_>) -> Result<(), Error> {
+        if self
+            .connection
+            .poll_close_timeout(&mut self.close_timer, cx)
+            .is_ready()
+        {
+            _ = Pin::new(self.inner.get_mut()).poll_shutdown(cx);
+            return Err(Error::CloseTimeout);
+        }
+
+        Ok(())
+    }
+
     /// Attempt to pull out the next frame from the [`Framed`] this stream and
     /// from that update the stream's internal state.
     ///
@@ -152,165 +302,254 @@ where
     ///
     /// This method returns an [`Error`] if reading from the stream fails or a
     /// protocol violation is encountered.
-    fn poll_next_frame(
+    fn poll_read_frame(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Frame, Error>>> {
         // In the case of Active or ClosedByUs, we want to receive more messages from
         // the remote. In the case of ClosedByPeer, we have to flush to make sure our
         // close acknowledge goes through.
-        if self.state == StreamState::CloseAcknowledged {
-            return Poll::Ready(None);
-        } else if self.state == StreamState::ClosedByPeer {
-            ready!(self.as_mut().poll_flush(cx))?;
-            self.state = StreamState::CloseAcknowledged;
+        if matches!(
+            self.connection.state,
+            StreamState::CloseAcknowledged | StreamState::Terminated
+        ) {
             return Poll::Ready(None);
+        } else if self.connection.state != StreamState::Active {
+            if let Err(e) = self.check_close_timeout(cx) {
+                return Poll::Ready(Some(Err(e)));
+            }
+
+            if self.connection.state == StreamState::ClosedByPeer {
+                ready!(self.as_mut().poll_flush(cx))?;
+                self.connection.state = StreamState::CloseAcknowledged;
+                return Poll::Ready(None);
+            }
         }
 
-        // If there are pending items, try to flush the sink
-        if !self.frame_queue.is_empty() {
+        // Send pings that are due and detect an unresponsive peer
+        let this = self.as_mut().get_mut();
+        if let Some(keepalive) = &mut this.keepalive {
+            while let Poll::Ready(event) = keepalive.poll(cx) {
+                if let Err(e) = this.connection.keepalive(event) {
+                    _ = self.as_mut().poll_flush(cx)?;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+
+        // If there are pending items, try to flush the sink. Too many of them
+        // have to be written before reading on
+        if self.connection.is_over_pending_limit() {
+            ready!(self.as_mut().poll_flush(cx))?;
+        } else if self.connection.has_pending_frames() {
             _ = self.as_mut().poll_flush(cx)?;
         }
 
-        let frame = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
-            Some(Ok(frame)) => frame,
-            Some(Err(e)) => {
-                if self.state == StreamState::ClosedByUs {
-                    self.state = StreamState::CloseAcknowledged;
-                } else {
-                    self.state = StreamState::ClosedByPeer;
-
-                    match &e {
-                        Error::Protocol(e) => self.queue_frame(Frame::from(e)),
-                        Error::PayloadTooLong { max_len, .. } => self.queue_frame(
-                            Message::close(
-                                Some(CloseCode::MESSAGE_TOO_BIG),
-                                &format!("max length: {max_len}"),
-                            )
-                            .into(),
-                        ),
-                        _ => {}
-                    }
+        let frame = if let Some(frame) = self.decoded.pop_front() {
+            frame
+        } else {
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(frame)) => frame,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => {
+                    // Release memory of large frames while waiting for the peer
+                    let config = self.connection.config;
+                    shrink_read_buffer(
+                        self.inner.read_buffer_mut(),
+                        config.read_buffer_capacity,
+                        config.read_buffer_high_water_mark,
+                    );
+
+                    return Poll::Pending;
                 }
-                return Poll::Ready(Some(Err(e)));
             }
-            None => return Poll::Ready(None),
         };
 
-        match frame.opcode {
-            OpCode::Close => match self.state {
-                StreamState::Active => {
-                    self.state = StreamState::ClosedByPeer;
+        // Decoded payloads of messages may not exceed the limit either
+        let max_len = self
+            .max_message_len()
+            .saturating_sub(self.partial.payload_len());
 
-                    let mut frame = frame.clone();
-                    frame.payload.truncate(2);
+        let frame = self.connection.receive(frame, max_len);
 
-                    self.queue_frame(frame);
-                }
-                // SAFETY: match statement at the start of the method ensures that this is not the
-                // case
-                StreamState::ClosedByPeer | StreamState::CloseAcknowledged => unsafe {
-                    unreachable_unchecked()
-                },
-                StreamState::ClosedByUs => {
-                    self.state = StreamState::CloseAcknowledged;
-                }
-            },
-            OpCode::Ping if self.state == StreamState::Active => {
-                let mut frame = frame.clone();
-                frame.opcode = OpCode::Pong;
+        if let (Some(keepalive), Ok(frame)) = (&mut self.keepalive, &frame) {
+            keepalive.receive(frame);
+        }
 
-                self.queue_frame(frame);
+        Poll::Ready(Some(frame))
+    }
+}
+
+impl<T> Stream for WebSocketStream<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    type Item = Result<Message, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let Some(frame) = ready!(self.as_mut().poll_next_frame(cx)?) else {
+                return Poll::Ready(None);
+            };
+
+            let max_len = self.max_message_len();
+
+            if let Some(message) = self.partial.push(frame, max_len) {
+                return Poll::Ready(Some(message));
             }
-            _ => {}
         }
+    }
+}
 
-        Poll::Ready(Some(Ok(frame)))
+impl<T> WebSocketStream<T> {
+    /// The maximum payload length of the message that is currently being
+    /// received.
+    pub(super) fn max_message_len(&self) -> usize {
+        self.inner.decoder().max_message_len()
     }
 
-    /// Masks and queues a frame for sending when [`poll_flush`] gets called.
-    fn queue_frame(&mut self, frame: Frame) {
-        if frame.opcode == OpCode::Close && self.state != StreamState::ClosedByPeer {
-            self.state = StreamState::ClosedByUs;
-        }
+    /// Discards the remaining frames of the message that is currently being
+    /// received.
+    pub(super) fn discard_message(&mut self) {
+        self.discard_message = true;
+    }
 
-        let (frame, mask): (Frame, Option<[u8; 4]>) = if self.inner.decoder().role == Role::Client {
-            #[cfg(feature = "client")]
-            {
-                let mut frame = frame;
-                let mut payload = BytesMut::from(frame.payload);
-                let mask = crate::rand::get_mask();
-                crate::mask::frame(mask, &mut payload, 0);
-                frame.payload = Payload::from(payload);
+    /// Returns the [`StreamState`] of the connection.
+    pub fn state(&self) -> StreamState {
+        self.connection.state
+    }
 
-                (frame, Some(mask))
-            }
-            #[cfg(not(feature = "client"))]
-            {
-                // SAFETY: This allows for making the dependency on random generators
-                // only required for clients, servers can avoid it entirely.
-                // Since it is not possible to create a stream with client role
-                // without the client builder (and that is locked behind the client feature),
-                // this branch is impossible to reach.
-                unsafe { std::hint::unreachable_unchecked() }
-            }
-        } else {
-            (frame, None)
-        };
+    /// Whether the connection is fully closed, i.e. the close handshake
+    /// completed or the connection was torn down without completing it.
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self.connection.state,
+            StreamState::CloseAcknowledged | StreamState::Terminated
+        )
+    }
+
+    /// Returns the amount of bytes that were queued for sending, but not yet
+    /// written to the underlying I/O.
+    pub fn buffered_amount(&self) -> usize {
+        self.connection.pending_bytes()
+    }
 
-        let header_len = frame.encode(&mut self.header_buf);
-        if mask.is_some() {
-            self.header_buf[1] |= 1 << 7;
+    /// Replaces the [`Config`] of the stream. The new configuration applies to
+    /// messages queued and read from now on. Changing the keepalive settings
+    /// restarts the keepalive timer.
+    pub fn set_config(&mut self, config: Config) {
+        let old = self.connection.config;
+
+        if old.ping_interval != config.ping_interval || old.pong_timeout != config.pong_timeout {
+            self.keepalive = Keepalive::new(&config);
         }
-        self.pending_bytes +=
-            header_len as usize + (u8::from(mask.is_some()) * 4) as usize + frame.payload.len();
-        self.frame_queue.push_back(EncodedFrame {
-            header: self.header_buf,
-            header_len,
-            mask,
-            payload: frame.payload,
-        });
+
+        self.connection.config = config;
+    }
+
+    /// Replaces the [`Limits`] of the stream, for example to relax them once
+    /// a client authenticated. The new limits apply to frames decoded from now
+    /// on, including the remaining frames of a partially received message.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.decoder_mut().limits = limits;
+    }
+
+    /// Returns the latest round-trip time measured from an automatic ping and
+    /// its pong, or `None` if automatic pings are disabled via
+    /// [`Config::ping_interval`] or none was answered yet.
+    ///
+    /// [`Config::ping_interval`]: crate::Config::ping_interval
+    pub fn rtt(&self) -> Option<Duration> {
+        self.keepalive.as_ref().and_then(Keepalive::rtt)
+    }
+
+    /// Returns the amount of memory in bytes currently held by the buffers of
+    /// the stream: the capacity of the read buffer and of a partially received
+    /// message, the bytes queued for sending and the buffer that outgoing
+    /// payloads of clients are masked into.
+    ///
+    /// Buffers that grew while receiving large messages can be shrunk when the
+    /// stream is idle via [`Config::read_buffer_high_water_mark`].
+    ///
+    /// [`Config::read_buffer_high_water_mark`]: crate::Config::read_buffer_high_water_mark
+    pub fn buffer_footprint(&self) -> usize {
+        self.inner.read_buffer().capacity()
+            + self.partial.payload_capacity()
+            + self.connection.pending_bytes()
+            + self.connection.mask_buf_capacity()
+    }
+
+    /// Returns the subprotocol that was negotiated in the handshake via the
+    /// `Sec-WebSocket-Protocol` header, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.connection.subprotocol()
+    }
+
+    /// Returns the close frame sent by the peer once it closed the connection,
+    /// which remains available after the stream ended. Close frames without a
+    /// code have [`CloseCode::NO_STATUS_RECEIVED`].
+    ///
+    /// [`CloseCode::NO_STATUS_RECEIVED`]: crate::CloseCode::NO_STATUS_RECEIVED
+    pub fn peer_close_frame(&self) -> Option<&CloseFrame> {
+        self.connection.peer_close_frame()
+    }
+
+    /// The maximum size of outgoing frames.
+    pub(super) fn frame_size(&self) -> usize {
+        self.connection.config.frame_size
+    }
+
+    /// Queues a frame for sending if the stream is still active.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the stream was closed.
+    pub(super) fn start_send_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        self.connection.start_send_frame(frame)
     }
 }
 
-impl<T> Stream for WebSocketStream<T>
+/// An adapter for a [`WebSocketStream`] that reads and writes individual
+/// [`Frame`]s instead of full messages, created by [`WebSocketStream::frames`].
+///
+/// The adapter implements [`futures_sink::Sink`] and [`futures_core::Stream`].
+#[derive(Debug)]
+pub struct Frames<'a, T> {
+    /// The stream that frames are read from and written to.
+    stream: &'a mut WebSocketStream<T>,
+}
+
+impl<T> Stream for Frames<'_, T>
 where
     T: AsyncRead + AsyncWrite + Unpin,
 {
-    type Item = Result<Message, Error>;
+    type Item = Result<Frame, Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let max_len = self.inner.decoder().limits.max_payload_len;
+        Pin::new(&mut *self.stream).poll_next_frame(cx)
+    }
+}
 
-        loop {
-            let (opcode, payload, fin) = match ready!(self.as_mut().poll_next_frame(cx)?) {
-                Some(frame) => (frame.opcode, frame.payload, frame.is_final),
-                None => return Poll::Ready(None),
-            };
-            let len = self.partial_payload.len() + payload.len();
+impl<T> Sink<Frame> for Frames<'_, T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    type Error = Error;
 
-            if opcode != OpCode::Continuation {
-                if fin {
-                    return Poll::Ready(Some(Ok(Message { opcode, payload })));
-                }
-                self.partial_opcode = opcode;
-                self.partial_payload = BytesMut::from(payload);
-            } else if len > max_len {
-                return Poll::Ready(Some(Err(Error::PayloadTooLong { len, max_len })));
-            } else {
-                self.partial_payload.extend_from_slice(&payload);
-            }
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<Message>::poll_ready(Pin::new(&mut *self.stream), cx)
+    }
 
-            if fin {
-                break;
-            }
-        }
+    fn start_send(mut self: Pin<&mut Self>, item: Frame) -> Result<(), Self::Error> {
+        self.stream.connection.start_send_checked_frame(item)
+    }
 
-        let opcode = replace(&mut self.partial_opcode, OpCode::Continuation);
-        let mut payload = Payload::from(take(&mut self.partial_payload));
-        payload.set_utf8_validated(opcode == OpCode::Text);
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<Message>::poll_flush(Pin::new(&mut *self.stream), cx)
+    }
 
-        Poll::Ready(Some(Ok(Message { opcode, payload })))
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Sink::<Message>::poll_close(Pin::new(&mut *self.stream), cx)
     }
 }
 
@@ -325,84 +564,38 @@ where
 {
     type Error = Error;
 
-    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        // tokio-util calls poll_flush when more than 8096 bytes are pending, otherwise
-        // it returns Ready. We will just replicate that behavior
-        if self.pending_bytes >= self.config.flush_threshold {
-            self.as_mut().poll_flush(cx)
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.connection.needs_flush() {
+            self.poll_flush(cx)
         } else {
             Poll::Ready(Ok(()))
         }
     }
 
     fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
-        if self.state != StreamState::Active {
-            return Err(Error::AlreadyClosed);
-        }
-
-        if item.opcode.is_control() || item.payload.len() <= self.config.frame_size {
-            let frame: Frame = item.into();
-            self.queue_frame(frame);
-        } else {
-            // Chunk the message into frames
-            for frame in item.into_frames(self.config.frame_size) {
-                self.queue_frame(frame);
-            }
-        }
-
-        Ok(())
+        self.connection.start_send(item)
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        // Borrow checker hacks... It needs this to understand that we can separately
-        // borrow the fields of the struct mutably
         let this = self.get_mut();
-        let frame_queue = &mut this.frame_queue;
-        let io = this.inner.get_mut();
-        let bytes_written = &mut this.bytes_written;
-        let pending_bytes = &mut this.pending_bytes;
-
-        while !frame_queue.is_empty() {
-            let frame = unsafe { frame_queue.front().unwrap_unchecked() };
-            let frame_header = unsafe { frame.header.get_unchecked(..frame.header_len as usize) };
-            let mut buf = frame_header
-                .chain(
-                    frame
-                        .mask
-                        .as_ref()
-                        .map(<[u8; 4]>::as_slice)
-                        .unwrap_or_default(),
-                )
-                .chain(&*frame.payload);
-            buf.advance(*bytes_written);
-
-            while buf.has_remaining() {
-                let n = ready!(poll_write_buf(Pin::new(io), cx, &mut buf))?;
-
-                if n == 0 {
-                    return Poll::Ready(Err(Error::Io(io::ErrorKind::WriteZero.into())));
-                }
+        this.connection.poll_flush(this.inner.get_mut(), cx)
+    }
 
-                *bytes_written += n;
-                *pending_bytes -= n;
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.connection.state == StreamState::Active {
+            self.connection.queue_frame(Frame::DEFAULT_CLOSE);
+        }
+        while let Some(item) = ready!(self.as_mut().poll_next(cx)) {
+            if let Err(Error::CloseTimeout) = item {
+                return Poll::Ready(Err(Error::CloseTimeout));
             }
-
-            frame_queue.pop_front();
-            *bytes_written = 0;
         }
 
-        ready!(Pin::new(io).poll_flush(cx))?;
-
-        Poll::Ready(Ok(()))
-    }
-
-    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        if self.state == StreamState::Active {
-            self.queue_frame(Frame::DEFAULT_CLOSE);
+        if self.as_mut().poll_flush(cx)?.is_pending() {
+            self.check_close_timeout(cx)?;
+            return Poll::Pending;
         }
-        while ready!(self.as_mut().poll_next(cx)).is_some() {}
 
-        ready!(self.as_mut().poll_flush(cx))?;
         Pin::new(self.inner.get_mut())
             .poll_shutdown(cx)
             .map_err(Error::Io)
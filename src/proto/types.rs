@@ -1,9 +1,14 @@
 //! Types required for the WebSocket protocol implementation.
 use std::{
-    cell::UnsafeCell, fmt, hint::unreachable_unchecked, mem::replace, num::NonZeroU16, ops::Deref,
+    fmt,
+    hint::unreachable_unchecked,
+    mem::replace,
+    num::NonZeroU16,
+    ops::{BitOr, BitOrAssign, Deref, RangeBounds},
+    time::Duration,
 };
 
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 use super::error::ProtocolError;
 use crate::utf8;
@@ -13,7 +18,7 @@ use crate::utf8;
 ///
 /// A fully assembled [`Message`] will never have a continuation opcode.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub(super) enum OpCode {
+pub enum OpCode {
     /// A continuation opcode. This will never be encountered in a full
     /// [`Message`].
     Continuation,
@@ -31,7 +36,8 @@ pub(super) enum OpCode {
 
 impl OpCode {
     /// Whether this is a control opcode (i.e. close, ping or pong).
-    pub(super) fn is_control(self) -> bool {
+    #[must_use]
+    pub fn is_control(self) -> bool {
         matches!(self, Self::Close | Self::Ping | Self::Pong)
     }
 }
@@ -65,6 +71,74 @@ impl From<OpCode> for u8 {
     }
 }
 
+/// The RSV bits of a WebSocket frame.
+///
+/// These bits are reserved for use by extensions and every bit may only be
+/// owned by a single negotiated extension. Multiple bits can be combined with
+/// the `|` operator.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Rsv(u8);
+
+impl Rsv {
+    /// No RSV bits.
+    pub const NONE: Self = Self(0);
+    /// The RSV1 bit.
+    pub const RSV1: Self = Self(0x40);
+    /// The RSV2 bit.
+    pub const RSV2: Self = Self(0x20);
+    /// The RSV3 bit.
+    pub const RSV3: Self = Self(0x10);
+
+    /// Creates the RSV bits from the first byte of a frame header.
+    pub(super) fn from_header_byte(byte: u8) -> Self {
+        Self(byte & 0x70)
+    }
+
+    /// Returns the RSV bits in the position of the first byte of a frame
+    /// header.
+    pub(super) fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether no RSV bits are set.
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all bits set in `other` are also set in `self`.
+    #[must_use]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether any bit set in `other` is also set in `self`.
+    #[must_use]
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns the bits set in `self` that are not set in `other`.
+    #[must_use]
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl BitOr for Rsv {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Rsv {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
 /// Close status code.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct CloseCode(NonZeroU16);
@@ -144,25 +218,118 @@ impl TryFrom<u16> for CloseCode {
     }
 }
 
-/// The websocket message payload storage. Internally implemented as a smart
-/// wrapper around [`Bytes`] and [`BytesMut`].
+/// The status code and reason of a close frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// The [`CloseCode`] of the frame.
+    code: CloseCode,
+    /// The reason for closing, at most [`CloseFrame::MAX_REASON_LEN`] bytes
+    /// long.
+    reason: String,
+}
+
+impl CloseFrame {
+    /// The maximum length of a close reason in bytes, the protocol-imposed
+    /// limit.
+    pub const MAX_REASON_LEN: usize = 123;
+
+    /// Creates a close frame with a code and reason. Reasons longer than
+    /// [`CloseFrame::MAX_REASON_LEN`] bytes are truncated at the last
+    /// character boundary that fits.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`ProtocolError::InvalidCloseCode`] if `code` is
+    /// one of the codes that must not be sent over the wire, such as
+    /// [`CloseCode::NO_STATUS_RECEIVED`].
+    pub fn new(code: CloseCode, reason: &str) -> Result<Self, ProtocolError> {
+        if !code.is_sendable() {
+            return Err(ProtocolError::InvalidCloseCode);
+        }
+
+        let mut len = reason.len().min(Self::MAX_REASON_LEN);
+        while !reason.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        Ok(Self {
+            code,
+            reason: reason[..len].to_owned(),
+        })
+    }
+
+    /// Parses the payload of a close frame that was validated by the codec.
+    /// Frames without payload have [`CloseCode::NO_STATUS_RECEIVED`].
+    pub(super) fn from_validated(payload: &[u8]) -> Self {
+        let Some((code, reason)) = payload.split_first_chunk::<2>() else {
+            return Self {
+                code: CloseCode::NO_STATUS_RECEIVED,
+                reason: String::new(),
+            };
+        };
+
+        Self {
+            // SAFETY: The codec validated the close code
+            code: unsafe { CloseCode::try_from(u16::from_be_bytes(*code)).unwrap_unchecked() },
+            // SAFETY: The codec validated the reason to be valid UTF-8
+            reason: unsafe { std::str::from_utf8_unchecked(reason) }.to_owned(),
+        }
+    }
+
+    /// Returns the [`CloseCode`] of the frame.
+    #[must_use]
+    pub fn code(&self) -> CloseCode {
+        self.code
+    }
+
+    /// Returns the reason for closing.
+    #[must_use]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl From<CloseFrame> for Message {
+    /// Converts a close frame into a close message. Frames with a code that may
+    /// not be sent over the wire, such as the [`CloseCode::NO_STATUS_RECEIVED`]
+    /// of an empty close frame of the peer, result in an empty close message.
+    fn from(value: CloseFrame) -> Self {
+        if !value.code.is_sendable() {
+            return Self::close(None, "");
+        }
+
+        let mut payload = BytesMut::with_capacity(2 + value.reason.len());
+        payload.put_u16(value.code.into());
+        payload.extend_from_slice(value.reason.as_bytes());
+
+        Self {
+            opcode: OpCode::Close,
+            payload: payload.into(),
+        }
+    }
+}
+
+/// The websocket message payload storage. Always backed by [`Bytes`].
+///
+/// Payloads can be created by using the `From<T>` implementations. Owned
+/// buffers such as [`BytesMut`] and [`Vec<u8>`] are frozen into [`Bytes`]
+/// without copying, and cloning a payload only increments a reference count.
 ///
-/// Payloads can be created by using the `From<T>` implementations. All of them
-/// use [`BytesMut`] under the hood, except when created using [`From<Bytes>`]
-/// with a reference counter greater than one or when using a static reference.
+/// Since the storage never changes after a payload was created, payloads need
+/// no interior mutability. This makes [`Payload`] and [`Message`] [`Sync`], so
+/// they can be shared between tasks, for example by reference or in an
+/// [`Arc`](std::sync::Arc).
 ///
-/// Sending the payloads is zero-copy, except when sending a payload backed by
-/// [`Bytes`] as a client to a server. The use of [`BytesMut`] as the backing
-/// storage where cheaply possible is recommended to ensure zero-copy sending.
+/// Sending the payloads is zero-copy.
 ///
 /// All conversions to other types are zero-cost, except [`Into<BytesMut>`] if
-/// the backing type is [`Bytes`] with a reference counter greater than one.
+/// the payload shares its memory with other payloads or [`Bytes`].
 ///
-/// [`From<Bytes>`]: #impl-From<Bytes>-for-Payload
 /// [`Into<BytesMut>`]: #impl-From<Payload>-for-BytesMut
+#[derive(Clone)]
 pub struct Payload {
     /// The raw payload data.
-    data: UnsafeCell<PayloadStorage>,
+    data: Bytes,
     /// Whether the payload data was validated to be valid UTF-8.
     utf8_validated: bool,
 }
@@ -171,77 +338,78 @@ impl Payload {
     /// Creates a new shared `Payload` from a static slice.
     const fn from_static(bytes: &'static [u8]) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(Bytes::from_static(bytes))),
+            data: Bytes::from_static(bytes),
             utf8_validated: false,
         }
     }
 
     /// Marks whether the payload contents were validated to be valid UTF-8.
-    pub(super) fn set_utf8_validated(&mut self, value: bool) {
+    pub(crate) fn set_utf8_validated(&mut self, value: bool) {
         self.utf8_validated = value;
     }
 
-    /// Shortens the buffer, keeping the first `len` bytes and dropping the
-    /// rest.
-    pub(super) fn truncate(&mut self, len: usize) {
-        match self.data.get_mut() {
-            PayloadStorage::Unique(b) => b.truncate(len),
-            PayloadStorage::Shared(b) => b.truncate(len),
-        }
+    /// Shortens the payload, keeping the first `len` bytes and dropping the
+    /// rest. Has no effect if `len` is greater than the length of the payload.
+    pub fn truncate(&mut self, len: usize) {
+        self.utf8_validated &= len >= self.len();
+        self.data.truncate(len);
     }
 
-    /// Splits the buffer into two at the given index.
-    fn split_to(&mut self, at: usize) -> Self {
-        // This is only used by the outgoing message frame iterator, so we do not care
-        // about the value of utf8_validated. For the sake of correctness (in case we
-        // split a utf8 codepoint), we set it to false.
+    /// Splits the payload into two at the given index. Afterwards, `self`
+    /// contains bytes `[at, len)` and the returned payload contains bytes
+    /// `[0, at)`. This is an O(1) operation that does not copy the payload.
+    ///
+    /// # Panics
+    ///
+    /// If `at > len`.
+    #[must_use = "consider Payload::advance if you don't need the other half"]
+    pub fn split_to(&mut self, at: usize) -> Self {
+        // The split could happen within a UTF-8 codepoint, so neither half is known
+        // to be valid UTF-8 anymore
         self.utf8_validated = false;
         Self {
-            data: UnsafeCell::new(match self.data.get_mut() {
-                PayloadStorage::Unique(b) => PayloadStorage::Unique(b.split_to(at)),
-                PayloadStorage::Shared(b) => PayloadStorage::Shared(b.split_to(at)),
-            }),
+            data: self.data.split_to(at),
             utf8_validated: false,
         }
     }
 
-    /// Converts the payload's internal representation to [`Bytes`].
-    fn as_bytes(&self) -> &Bytes {
-        if let PayloadStorage::Shared(bytes) = self.as_ref() {
-            bytes
-        } else {
-            // SAFETY: No concurrent access is possible as Payload is !Sync and
-            // `0` is not read again before it's overwritten.
-            unsafe {
-                let payload = self.data.get().read();
-                let bytes = match payload {
-                    PayloadStorage::Unique(p) => p.freeze(),
-                    PayloadStorage::Shared(_) => unreachable_unchecked(),
-                };
-                self.data.get().write(PayloadStorage::Shared(bytes));
-            }
-            match self.as_ref() {
-                // SAFETY: We just wrote `Shared` into `value`
-                PayloadStorage::Unique(_) => unsafe { unreachable_unchecked() },
-                PayloadStorage::Shared(p) => p,
-            }
+    /// Splits the payload into two at the given index. Afterwards, `self`
+    /// contains bytes `[0, at)` and the returned payload contains bytes
+    /// `[at, len)`. This is an O(1) operation that does not copy the payload.
+    ///
+    /// # Panics
+    ///
+    /// If `at > len`.
+    #[must_use = "consider Payload::truncate if you don't need the other half"]
+    pub fn split_off(&mut self, at: usize) -> Self {
+        self.utf8_validated = false;
+        Self {
+            data: self.data.split_off(at),
+            utf8_validated: false,
         }
     }
-}
 
-impl AsRef<PayloadStorage> for Payload {
-    fn as_ref(&self) -> &PayloadStorage {
-        // SAFETY: No outstanding mutable references exists.
-        unsafe { &*self.data.get() }
+    /// Removes the first `cnt` bytes of the payload.
+    ///
+    /// # Panics
+    ///
+    /// If `cnt > len`.
+    pub fn advance(&mut self, cnt: usize) {
+        self.utf8_validated = false;
+        self.data.advance(cnt);
     }
-}
 
-impl Clone for Payload {
-    fn clone(&self) -> Self {
-        let bytes = self.as_bytes();
+    /// Returns a payload of the given range of this payload that shares its
+    /// memory without copying.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds.
+    #[must_use]
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(bytes.clone())),
-            utf8_validated: self.utf8_validated,
+            data: self.data.slice(range),
+            utf8_validated: false,
         }
     }
 }
@@ -250,30 +418,21 @@ impl Deref for Payload {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        match self.as_ref() {
-            PayloadStorage::Unique(b) => b,
-            PayloadStorage::Shared(b) => b,
-        }
+        &self.data
     }
 }
 
 impl fmt::Debug for Payload {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("Payload").field(self.as_ref()).finish()
+        f.debug_tuple("Payload").field(&self.data).finish()
     }
 }
 
 impl From<Bytes> for Payload {
     fn from(value: Bytes) -> Self {
-        match value.try_into_mut() {
-            Ok(value) => Self {
-                data: UnsafeCell::new(PayloadStorage::Unique(value)),
-                utf8_validated: false,
-            },
-            Err(value) => Self {
-                data: UnsafeCell::new(PayloadStorage::Shared(value)),
-                utf8_validated: false,
-            },
+        Self {
+            data: value,
+            utf8_validated: false,
         }
     }
 }
@@ -281,7 +440,7 @@ impl From<Bytes> for Payload {
 impl From<BytesMut> for Payload {
     fn from(value: BytesMut) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Unique(value)),
+            data: value.freeze(),
             utf8_validated: false,
         }
     }
@@ -289,29 +448,20 @@ impl From<BytesMut> for Payload {
 
 impl From<Payload> for Bytes {
     fn from(value: Payload) -> Self {
-        match value.data.into_inner() {
-            PayloadStorage::Unique(p) => p.freeze(),
-            PayloadStorage::Shared(p) => p,
-        }
+        value.data
     }
 }
 
 impl From<Payload> for BytesMut {
     fn from(value: Payload) -> Self {
-        match value.data.into_inner() {
-            PayloadStorage::Unique(p) => p,
-            PayloadStorage::Shared(p) => p.into(),
-        }
+        value.data.into()
     }
 }
 
 impl From<Vec<u8>> for Payload {
     fn from(value: Vec<u8>) -> Self {
-        // BytesMut::from_iter goes through a specialization in std if the iterator is a
-        // Vec, effectively allowing us to use BytesMut::from_vec which isn't
-        // exposed in bytes. See https://github.com/tokio-rs/bytes/issues/723 for details.
         Self {
-            data: UnsafeCell::new(PayloadStorage::Unique(BytesMut::from_iter(value))),
+            data: Bytes::from(value),
             utf8_validated: false,
         }
     }
@@ -319,11 +469,8 @@ impl From<Vec<u8>> for Payload {
 
 impl From<String> for Payload {
     fn from(value: String) -> Self {
-        // See From<Vec<u8>> impl for reasoning behind this.
         Self {
-            data: UnsafeCell::new(PayloadStorage::Unique(BytesMut::from_iter(
-                value.into_bytes(),
-            ))),
+            data: Bytes::from(value),
             utf8_validated: true,
         }
     }
@@ -332,7 +479,7 @@ impl From<String> for Payload {
 impl From<&'static [u8]> for Payload {
     fn from(value: &'static [u8]) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(Bytes::from_static(value))),
+            data: Bytes::from_static(value),
             utf8_validated: false,
         }
     }
@@ -341,19 +488,167 @@ impl From<&'static [u8]> for Payload {
 impl From<&'static str> for Payload {
     fn from(value: &'static str) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(Bytes::from_static(value.as_bytes()))),
+            data: Bytes::from_static(value.as_bytes()),
             utf8_validated: true,
         }
     }
 }
 
-/// [`Payload`] backend.
-#[derive(Debug)]
-enum PayloadStorage {
-    /// Unique data.
-    Unique(BytesMut),
-    /// Shared data.
-    Shared(Bytes),
+/// A [`Payload`] that is known to be valid UTF-8, such as the payload of a
+/// received text message. It is as cheaply clonable as [`Payload`] and derefs
+/// to [`str`], so text can be passed around without validating or copying it
+/// again.
+///
+/// Utf8 payloads can be created from strings or by validating other payloads
+/// via the `TryFrom<T>` implementations.
+#[derive(Clone, Debug)]
+pub struct Utf8Payload(Payload);
+
+impl Utf8Payload {
+    /// Creates a UTF-8 payload from a payload that is known to be valid
+    /// UTF-8.
+    fn new_unchecked(mut payload: Payload) -> Self {
+        payload.utf8_validated = true;
+
+        Self(payload)
+    }
+
+    /// Returns the payload as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: The payload is valid UTF-8
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+
+    /// Returns a UTF-8 payload of the given range of this payload that shares
+    /// its memory without copying.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds or does not start and end on character
+    /// boundaries.
+    #[must_use]
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        assert!(
+            self.as_str().get(range).is_some(),
+            "range is out of bounds or not on character boundaries"
+        );
+
+        Self::new_unchecked(self.0.slice(range))
+    }
+
+    /// Returns the underlying [`Payload`].
+    #[must_use]
+    pub fn into_payload(self) -> Payload {
+        self.0
+    }
+}
+
+impl Deref for Utf8Payload {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Utf8Payload {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for Utf8Payload {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Utf8Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq for Utf8Payload {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Utf8Payload {}
+
+impl PartialEq<str> for Utf8Payload {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Utf8Payload {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl From<String> for Utf8Payload {
+    fn from(value: String) -> Self {
+        Self::new_unchecked(value.into())
+    }
+}
+
+impl From<&'static str> for Utf8Payload {
+    fn from(value: &'static str) -> Self {
+        Self::new_unchecked(value.into())
+    }
+}
+
+impl TryFrom<Payload> for Utf8Payload {
+    type Error = ProtocolError;
+
+    fn try_from(value: Payload) -> Result<Self, Self::Error> {
+        if !value.utf8_validated {
+            utf8::parse_str(&value)?;
+        }
+
+        Ok(Self::new_unchecked(value))
+    }
+}
+
+impl TryFrom<Bytes> for Utf8Payload {
+    type Error = ProtocolError;
+
+    fn try_from(value: Bytes) -> Result<Self, Self::Error> {
+        Payload::from(value).try_into()
+    }
+}
+
+impl TryFrom<BytesMut> for Utf8Payload {
+    type Error = ProtocolError;
+
+    fn try_from(value: BytesMut) -> Result<Self, Self::Error> {
+        Payload::from(value).try_into()
+    }
+}
+
+impl TryFrom<Vec<u8>> for Utf8Payload {
+    type Error = ProtocolError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Payload::from(value).try_into()
+    }
+}
+
+impl From<Utf8Payload> for Payload {
+    fn from(value: Utf8Payload) -> Self {
+        value.0
+    }
+}
+
+impl From<Utf8Payload> for Bytes {
+    fn from(value: Utf8Payload) -> Self {
+        value.0.into()
+    }
 }
 
 /// A WebSocket message. This is cheaply clonable and uses [`Payload`] as the
@@ -379,6 +674,20 @@ impl Message {
         }
     }
 
+    /// Create a new text message after validating that the payload is valid
+    /// UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`ProtocolError::InvalidUtf8`] if the payload is
+    /// not valid UTF-8.
+    pub fn text_checked<P: Into<Payload>>(payload: P) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            opcode: OpCode::Text,
+            payload: Utf8Payload::try_from(payload.into())?.into(),
+        })
+    }
+
     /// Create a new binary message.
     #[must_use]
     pub fn binary<P: Into<Payload>>(payload: P) -> Self {
@@ -391,6 +700,9 @@ impl Message {
     /// Create a new close message. If an non-empty reason is specified, a
     /// [`CloseCode`] must be specified for it to be included.
     ///
+    /// A [`CloseFrame`] can be converted into a close message without
+    /// panicking on long reasons.
+    ///
     /// # Panics
     /// If `code` is present and the `reason` exceeds 123 bytes,
     /// the protocol-imposed limit.
@@ -503,6 +815,26 @@ impl Message {
         })
     }
 
+    /// Returns the payload as a [`Utf8Payload`] and consumes the message if it
+    /// is a text message. The payload is only validated again if the message
+    /// was created via [`Message::text`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the message back if it is not a text message or its
+    /// payload is not valid UTF-8.
+    pub fn into_text(self) -> Result<Utf8Payload, Self> {
+        if self.opcode != OpCode::Text {
+            return Err(self);
+        }
+
+        if self.payload.utf8_validated || utf8::parse_str(&self.payload).is_ok() {
+            Ok(Utf8Payload::new_unchecked(self.payload))
+        } else {
+            Err(self)
+        }
+    }
+
     /// Returns the [`CloseCode`] and close reason if the message is a close
     /// message.
     pub fn as_close(&self) -> Option<(CloseCode, &str)> {
@@ -531,6 +863,55 @@ impl Message {
         })
     }
 
+    /// Returns a [`MessageRef`] that borrows the payload of the message in a
+    /// form matching its type.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic when the message was created via
+    /// [`Message::text`] with invalid UTF-8.
+    #[must_use]
+    pub fn kind(&self) -> MessageRef<'_> {
+        match self.opcode {
+            // SAFETY: The opcode is Text
+            OpCode::Text => MessageRef::Text(unsafe { self.as_text().unwrap_unchecked() }),
+            OpCode::Binary => MessageRef::Binary(&self.payload),
+            OpCode::Ping => MessageRef::Ping(&self.payload),
+            OpCode::Pong => MessageRef::Pong(&self.payload),
+            OpCode::Close => MessageRef::Close(self.close_frame()),
+            // SAFETY: Messages are never created with a continuation opcode
+            OpCode::Continuation => unsafe { unreachable_unchecked() },
+        }
+    }
+
+    /// Returns the payload of the message as a [`MessageKind`] matching its
+    /// type and consumes the message.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic when the message was created via
+    /// [`Message::text`] with invalid UTF-8.
+    #[must_use]
+    pub fn into_kind(self) -> MessageKind {
+        match self.opcode {
+            OpCode::Text => MessageKind::Text(self.into_text().unwrap_or_else(|_| {
+                panic!("called into_kind on message created from payload with invalid utf-8")
+            })),
+            OpCode::Binary => MessageKind::Binary(self.payload),
+            OpCode::Ping => MessageKind::Ping(self.payload),
+            OpCode::Pong => MessageKind::Pong(self.payload),
+            OpCode::Close => MessageKind::Close(self.close_frame()),
+            // SAFETY: Messages are never created with a continuation opcode
+            OpCode::Continuation => unsafe { unreachable_unchecked() },
+        }
+    }
+
+    /// Parses the payload of a close message into a [`CloseFrame`], or `None`
+    /// if it has no close code.
+    fn close_frame(&self) -> Option<CloseFrame> {
+        (!self.payload.is_empty()).then(|| CloseFrame::from_validated(&self.payload))
+    }
+
     /// Returns an iterator over frames of `frame_size` length to split this
     /// message into.
     pub(super) fn into_frames(self, frame_size: usize) -> MessageFrames {
@@ -540,6 +921,117 @@ impl Message {
             opcode: self.opcode,
         }
     }
+
+    /// Encodes the message into frames once, split according to
+    /// [`Config::frame_size`], so that it can be sent to many connections
+    /// without encoding it again for each of them.
+    ///
+    /// The returned [`EncodedMessage`] is cheaply clonable and can be sent
+    /// via [`WebSocketStream::send_encoded`] and [`WriteHalf::send_encoded`].
+    ///
+    /// [`WebSocketStream::send_encoded`]: super::WebSocketStream::send_encoded
+    /// [`WriteHalf::send_encoded`]: super::WriteHalf::send_encoded
+    #[must_use]
+    pub fn prepare(self, config: &Config) -> EncodedMessage {
+        let frame_size = if self.opcode.is_control() {
+            usize::MAX
+        } else {
+            config.frame_size
+        };
+        let mut header = [0; 10];
+        let mut frames = Vec::with_capacity(self.payload.len() + 10);
+
+        for frame in self.clone().into_frames(frame_size) {
+            let header_len = frame.encode(&mut header);
+            frames.extend_from_slice(&header[..header_len as usize]);
+            frames.extend_from_slice(&frame.payload);
+        }
+
+        EncodedMessage {
+            message: self,
+            frames: Bytes::from(frames),
+        }
+    }
+}
+
+/// A [`Message`] that was encoded into frames once by [`Message::prepare`] to
+/// broadcast it to many connections.
+///
+/// The frames are shared between all clones, so sending the message from a
+/// server only adds them to the queue of outgoing frames. Clients mask their
+/// frames and extensions may transform them, so client streams and streams
+/// with negotiated extensions send the message like any other instead.
+#[derive(Debug, Clone)]
+pub struct EncodedMessage {
+    /// The message that was encoded.
+    pub(super) message: Message,
+    /// The unmasked frames of the message, including their headers.
+    pub(super) frames: Bytes,
+}
+
+impl EncodedMessage {
+    /// Returns the message that was encoded.
+    #[must_use]
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Returns the encoded frames of the message, including their headers.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.frames
+    }
+
+    /// Returns the message that was encoded, dropping the frames.
+    #[must_use]
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+}
+
+/// A borrowed view of a [`Message`] by type, returned by [`Message::kind`].
+#[derive(Debug, Clone)]
+pub enum MessageRef<'a> {
+    /// A text message.
+    Text(&'a str),
+    /// A binary message.
+    Binary(&'a Payload),
+    /// A ping message.
+    Ping(&'a Payload),
+    /// A pong message.
+    Pong(&'a Payload),
+    /// A close message, with its code and reason if it has a code.
+    Close(Option<CloseFrame>),
+}
+
+/// The payload of a [`Message`] by type, returned by [`Message::into_kind`].
+#[derive(Debug, Clone)]
+pub enum MessageKind {
+    /// A text message.
+    Text(Utf8Payload),
+    /// A binary message.
+    Binary(Payload),
+    /// A ping message.
+    Ping(Payload),
+    /// A pong message.
+    Pong(Payload),
+    /// A close message, with its code and reason if it has a code.
+    Close(Option<CloseFrame>),
+}
+
+impl From<MessageKind> for Message {
+    fn from(value: MessageKind) -> Self {
+        let (opcode, payload) = match value {
+            MessageKind::Text(payload) => (OpCode::Text, payload.into()),
+            MessageKind::Binary(payload) => (OpCode::Binary, payload),
+            MessageKind::Ping(payload) => (OpCode::Ping, payload),
+            MessageKind::Pong(payload) => (OpCode::Pong, payload),
+            MessageKind::Close(Some(frame)) => return frame.into(),
+            MessageKind::Close(None) => (OpCode::Close, Payload::from_static(&[])),
+        };
+
+        Self { opcode, payload }
+    }
 }
 
 /// Iterator over frames of a chunked message.
@@ -566,6 +1058,7 @@ impl Iterator for MessageFrames {
             Frame {
                 opcode: replace(&mut self.opcode, OpCode::Continuation),
                 is_final: self.payload.is_empty(),
+                rsv: Rsv::NONE,
                 payload,
             }
         })
@@ -576,11 +1069,24 @@ impl Iterator for MessageFrames {
 /// [`WebSocketStream`] to prevent high memory usage caused by malicious actors.
 ///
 /// [`WebSocketStream`]: super::WebSocketStream
+#[allow(clippy::struct_field_names)]
 #[derive(Debug, Clone, Copy)]
 pub struct Limits {
     /// The maximum allowed payload length. The default
     /// is 64 MiB.
     pub(super) max_payload_len: usize,
+    /// The maximum allowed payload length of a single frame. The default is
+    /// no limit other than the maximum payload length.
+    pub(super) max_frame_len: usize,
+    /// The maximum allowed number of frames per message. The default is no
+    /// limit.
+    pub(super) max_fragments: usize,
+    /// The maximum allowed payload length of text messages. The default is no
+    /// limit other than the maximum payload length.
+    pub(super) max_text_len: usize,
+    /// The maximum allowed payload length of binary messages. The default is
+    /// no limit other than the maximum payload length.
+    pub(super) max_binary_len: usize,
 }
 
 impl Limits {
@@ -589,6 +1095,10 @@ impl Limits {
     pub fn unlimited() -> Self {
         Self {
             max_payload_len: usize::MAX,
+            max_frame_len: usize::MAX,
+            max_fragments: usize::MAX,
+            max_text_len: usize::MAX,
+            max_binary_len: usize::MAX,
         }
     }
 
@@ -600,12 +1110,76 @@ impl Limits {
 
         self
     }
+
+    /// Sets the maximum allowed payload length of a single frame, including
+    /// control frames. Exceeding it closes the connection with
+    /// [`CloseCode::MESSAGE_TOO_BIG`]. `None` equals no limit. The default is
+    /// `None`.
+    #[must_use]
+    pub fn max_frame_len(mut self, size: Option<usize>) -> Self {
+        self.max_frame_len = size.unwrap_or(usize::MAX);
+
+        self
+    }
+
+    /// Sets the maximum allowed number of frames that a message may be
+    /// fragmented into. Control frames in between are not counted. Exceeding
+    /// it closes the connection with [`CloseCode::POLICY_VIOLATION`]. `None`
+    /// equals no limit. The default is `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is `Some(0)`.
+    #[must_use]
+    pub fn max_fragments(mut self, count: Option<usize>) -> Self {
+        assert!(count != Some(0), "a message consists of at least one frame");
+        self.max_fragments = count.unwrap_or(usize::MAX);
+
+        self
+    }
+
+    /// Sets the maximum allowed payload length of text messages, in addition
+    /// to the maximum payload length. Exceeding it closes the connection with
+    /// [`CloseCode::MESSAGE_TOO_BIG`]. `None` equals no limit. The default is
+    /// `None`.
+    #[must_use]
+    pub fn max_text_len(mut self, size: Option<usize>) -> Self {
+        self.max_text_len = size.unwrap_or(usize::MAX);
+
+        self
+    }
+
+    /// Sets the maximum allowed payload length of binary messages, in addition
+    /// to the maximum payload length. Exceeding it closes the connection with
+    /// [`CloseCode::MESSAGE_TOO_BIG`]. `None` equals no limit. The default is
+    /// `None`.
+    #[must_use]
+    pub fn max_binary_len(mut self, size: Option<usize>) -> Self {
+        self.max_binary_len = size.unwrap_or(usize::MAX);
+
+        self
+    }
+
+    /// The maximum allowed payload length of messages with the given opcode.
+    pub(super) fn max_message_len(&self, opcode: OpCode) -> usize {
+        let max_len = match opcode {
+            OpCode::Text => self.max_text_len,
+            OpCode::Binary => self.max_binary_len,
+            _ => usize::MAX,
+        };
+
+        max_len.min(self.max_payload_len)
+    }
 }
 
 impl Default for Limits {
     fn default() -> Self {
         Self {
             max_payload_len: 64 * 1024 * 1024,
+            max_frame_len: usize::MAX,
+            max_fragments: usize::MAX,
+            max_text_len: usize::MAX,
+            max_binary_len: usize::MAX,
         }
     }
 }
@@ -624,6 +1198,29 @@ pub struct Config {
     /// Threshold of queued up bytes after which the underlying I/O is flushed
     /// before the sink is declared ready. The default is 8 KiB.
     pub(super) flush_threshold: usize,
+    /// Amount of queued up bytes beyond which the stream stops reading until
+    /// they were flushed. The default is `None`.
+    pub(super) max_pending_bytes: Option<usize>,
+    /// Whether pings are answered with a pong automatically. The default is
+    /// `true`.
+    pub(super) auto_pong: bool,
+    /// Whether received pings and pongs are yielded to the reader of the
+    /// stream. The default is `true`.
+    pub(super) yield_pings_and_pongs: bool,
+    /// Capacity of the read buffer when the stream is created or the buffer
+    /// is shrunk. The default is 8 KiB.
+    pub(super) read_buffer_capacity: usize,
+    /// Capacity of the read buffer beyond which it is shrunk once the stream
+    /// is idle. The default is `None`.
+    pub(super) read_buffer_high_water_mark: Option<usize>,
+    /// Interval at which pings are sent automatically. The default is `None`.
+    pub(super) ping_interval: Option<Duration>,
+    /// Time after an automatic ping within which the peer has to send a frame.
+    /// The default is `None`.
+    pub(super) pong_timeout: Option<Duration>,
+    /// Time within which the close handshake has to complete. The default is
+    /// `None`.
+    pub(super) close_timeout: Option<Duration>,
 }
 
 impl Config {
@@ -651,6 +1248,117 @@ impl Config {
 
         self
     }
+
+    /// Sets the amount of queued up outgoing bytes beyond which the stream
+    /// stops reading from the peer until the queue was flushed, and the sink
+    /// is not declared ready. This bounds the memory used by replies to a peer
+    /// that keeps sending but does not read. The default is `None`, which
+    /// places no limit on the queue.
+    #[must_use]
+    pub fn max_pending_bytes(mut self, max: Option<usize>) -> Self {
+        self.max_pending_bytes = max;
+
+        self
+    }
+
+    /// Sets whether pings from the peer are answered with a pong of the same
+    /// payload automatically. If disabled, the application has to send pongs
+    /// itself, which requires [`Config::yield_pings_and_pongs`] to be enabled
+    /// to see the pings. Close frames are always answered automatically. The
+    /// default is `true`.
+    #[must_use]
+    pub fn auto_pong(mut self, auto_pong: bool) -> Self {
+        self.auto_pong = auto_pong;
+
+        self
+    }
+
+    /// Sets whether pings and pongs received from the peer are yielded when
+    /// reading from the stream. If disabled, they are handled silently and only
+    /// data and close messages are yielded. The default is `true`.
+    #[must_use]
+    pub fn yield_pings_and_pongs(mut self, yield_pings_and_pongs: bool) -> Self {
+        self.yield_pings_and_pongs = yield_pings_and_pongs;
+
+        self
+    }
+
+    /// Sets the capacity that the buffer for reading from the underlying I/O
+    /// starts out with and is shrunk back to. The buffer grows as needed to
+    /// hold larger frames. The default is 8 KiB.
+    #[must_use]
+    pub fn read_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.read_buffer_capacity = capacity;
+
+        self
+    }
+
+    /// Sets the capacity of the read buffer beyond which it is shrunk back to
+    /// [`Config::read_buffer_capacity`] once the stream is idle, i.e. it waits
+    /// for data from the peer and no partial frame is buffered. Without it, a
+    /// single large message keeps the buffer at its peak capacity for the rest
+    /// of the connection. The default is `None`, which never shrinks the
+    /// buffer.
+    #[must_use]
+    pub fn read_buffer_high_water_mark(mut self, high_water_mark: Option<usize>) -> Self {
+        self.read_buffer_high_water_mark = high_water_mark;
+
+        self
+    }
+
+    /// Sets the interval at which the stream sends pings on its own while it
+    /// is being read from. The latest round-trip time measured from the
+    /// replies is available via [`WebSocketStream::rtt`]. The default is
+    /// `None`, which disables automatic pings.
+    ///
+    /// Automatic pings require the time driver of the tokio runtime to be
+    /// enabled.
+    ///
+    /// # Panics
+    ///
+    /// If `interval` is zero.
+    ///
+    /// [`WebSocketStream::rtt`]: super::WebSocketStream::rtt
+    #[must_use]
+    pub fn ping_interval(mut self, interval: Option<Duration>) -> Self {
+        assert_ne!(
+            interval,
+            Some(Duration::ZERO),
+            "ping_interval must be non-zero"
+        );
+        self.ping_interval = interval;
+
+        self
+    }
+
+    /// Sets the time after an automatic ping within which the peer has to send
+    /// a pong or any other frame. Otherwise, the stream closes the connection
+    /// and returns [`Error::KeepaliveTimeout`]. Only has an effect if
+    /// [`Config::ping_interval`] is set. The default is `None`, which means
+    /// that the peer is never considered unresponsive.
+    ///
+    /// [`Error::KeepaliveTimeout`]: crate::Error::KeepaliveTimeout
+    #[must_use]
+    pub fn pong_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pong_timeout = timeout;
+
+        self
+    }
+
+    /// Sets the time within which the close handshake has to complete once
+    /// either end started closing the connection. This covers waiting for the
+    /// close frame of the peer as well as flushing our own. Once it expires,
+    /// the stream stops waiting, shuts down the underlying I/O without
+    /// awaiting it and reports [`Error::CloseTimeout`]. The default is `None`,
+    /// which waits indefinitely.
+    ///
+    /// [`Error::CloseTimeout`]: crate::Error::CloseTimeout
+    #[must_use]
+    pub fn close_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.close_timeout = timeout;
+
+        self
+    }
 }
 
 impl Default for Config {
@@ -658,6 +1366,14 @@ impl Default for Config {
         Self {
             frame_size: 4 * 1024 * 1024,
             flush_threshold: 8 * 1024,
+            max_pending_bytes: None,
+            auto_pong: true,
+            yield_pings_and_pongs: true,
+            read_buffer_capacity: 8 * 1024,
+            read_buffer_high_water_mark: None,
+            ping_interval: None,
+            pong_timeout: None,
+            close_timeout: None,
         }
     }
 }
@@ -671,9 +1387,11 @@ pub(crate) enum Role {
     Server,
 }
 
-/// The connection state of the stream.
-#[derive(Debug, PartialEq)]
-pub(super) enum StreamState {
+/// The connection state of a [`WebSocketStream`].
+///
+/// [`WebSocketStream`]: super::WebSocketStream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
     /// The connection is fully active and no close has been initiated.
     Active,
     /// The connection has been closed by the peer, but not yet acknowledged by
@@ -684,31 +1402,86 @@ pub(super) enum StreamState {
     /// The close has been acknowledged by the end that did not initiate the
     /// close.
     CloseAcknowledged,
+    /// The connection was torn down without completing the close handshake.
+    Terminated,
 }
 
 /// A frame of a WebSocket [`Message`].
 #[derive(Clone, Debug)]
-pub(super) struct Frame {
+pub struct Frame {
     /// The [`OpCode`] of the frame.
-    pub opcode: OpCode,
+    pub(crate) opcode: OpCode,
     /// Whether this is the last frame of a message.
-    pub is_final: bool,
+    pub(crate) is_final: bool,
+    /// The RSV bits of the frame, reserved for use by extensions.
+    pub(crate) rsv: Rsv,
     /// The payload bytes of the frame.
-    pub payload: Payload,
+    pub(crate) payload: Payload,
 }
 
 impl Frame {
     /// Default close frame.
     #[allow(clippy::declare_interior_mutable_const)]
-    pub const DEFAULT_CLOSE: Self = Self {
+    pub(crate) const DEFAULT_CLOSE: Self = Self {
         opcode: OpCode::Close,
         is_final: true,
+        rsv: Rsv::NONE,
         payload: Payload::from_static(&CloseCode::NORMAL_CLOSURE.0.get().to_be_bytes()),
     };
 
+    /// Creates a new frame without any RSV bits set.
+    pub fn new<P: Into<Payload>>(opcode: OpCode, payload: P, is_final: bool) -> Self {
+        Self {
+            opcode,
+            is_final,
+            rsv: Rsv::NONE,
+            payload: payload.into(),
+        }
+    }
+
+    /// Returns the [`OpCode`] of the frame.
+    #[must_use]
+    pub fn opcode(&self) -> OpCode {
+        self.opcode
+    }
+
+    /// Whether this is the last frame of a message.
+    #[must_use]
+    pub fn is_final(&self) -> bool {
+        self.is_final
+    }
+
+    /// Returns the RSV bits of the frame.
+    #[must_use]
+    pub fn rsv(&self) -> Rsv {
+        self.rsv
+    }
+
+    /// Sets the RSV bits of the frame.
+    pub fn set_rsv(&mut self, rsv: Rsv) {
+        self.rsv = rsv;
+    }
+
+    /// Returns a reference to the frame payload.
+    #[must_use]
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    /// Replaces the payload of the frame.
+    pub fn set_payload<P: Into<Payload>>(&mut self, payload: P) {
+        self.payload = payload.into();
+    }
+
+    /// Returns the payload of the frame.
+    #[must_use]
+    pub fn into_payload(self) -> Payload {
+        self.payload
+    }
+
     /// Encode the frame head into `out`, returning how many bytes were written.
-    pub fn encode(&self, out: &mut [u8; 10]) -> u8 {
-        out[0] = (u8::from(self.is_final) << 7) | u8::from(self.opcode);
+    pub(crate) fn encode(&self, out: &mut [u8; 10]) -> u8 {
+        out[0] = (u8::from(self.is_final) << 7) | self.rsv.bits() | u8::from(self.opcode);
         if u16::try_from(self.payload.len()).is_err() {
             out[1] = 127;
             let len = u64::try_from(self.payload.len()).unwrap();
@@ -731,6 +1504,7 @@ impl From<Message> for Frame {
         Self {
             opcode: value.opcode,
             is_final: true,
+            rsv: Rsv::NONE,
             payload: value.payload,
         }
     }
@@ -742,6 +1516,11 @@ impl From<&ProtocolError> for Frame {
             ProtocolError::InvalidUtf8 => {
                 Message::close(Some(CloseCode::INVALID_FRAME_PAYLOAD_DATA), "invalid utf8")
             }
+            #[cfg(feature = "permessage-deflate")]
+            ProtocolError::InvalidCompressedData => Message::close(
+                Some(CloseCode::INVALID_FRAME_PAYLOAD_DATA),
+                "invalid compressed data",
+            ),
             _ => Message::close(Some(CloseCode::PROTOCOL_ERROR), val.as_str()),
         }
         .into()
@@ -0,0 +1,243 @@
+//! Streaming writer for the payload of a single message.
+use std::{
+    io,
+    mem::replace,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use bytes::BytesMut;
+use futures_sink::Sink;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::{
+    error::ProtocolError,
+    split::WriteHalf,
+    stream::WebSocketStream,
+    types::{Frame, Message, OpCode, Payload, Rsv},
+};
+use crate::{utf8::Validator, CloseCode, Error};
+
+/// The stream or write half that a [`MessageWriter`] queues frames on.
+#[derive(Debug)]
+pub(super) enum Target<'a, T> {
+    /// A full stream, which is not read from while the writer borrows it.
+    Stream(&'a mut WebSocketStream<T>),
+    /// The write half of a split stream, whose read half keeps reading.
+    WriteHalf(&'a mut WriteHalf<T>),
+}
+
+impl<T> Target<'_, T> {
+    /// The maximum size of outgoing frames.
+    fn frame_size(&self) -> usize {
+        match self {
+            Self::Stream(stream) => stream.frame_size(),
+            Self::WriteHalf(write) => write.frame_size(),
+        }
+    }
+
+    /// Queues a frame for sending if the connection is still active.
+    fn start_send_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        match self {
+            Self::Stream(stream) => stream.start_send_frame(frame),
+            Self::WriteHalf(write) => write.start_send_frame(frame),
+        }
+    }
+}
+
+impl<T> Target<'_, T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Flushes queued frames if too many are pending.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        match self {
+            Self::Stream(stream) => Sink::<Message>::poll_ready(Pin::new(&mut **stream), cx),
+            Self::WriteHalf(write) => Sink::<Message>::poll_ready(Pin::new(&mut **write), cx),
+        }
+    }
+
+    /// Writes all queued frames and flushes the underlying I/O.
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        match self {
+            Self::Stream(stream) => Sink::<Message>::poll_flush(Pin::new(&mut **stream), cx),
+            Self::WriteHalf(write) => Sink::<Message>::poll_flush(Pin::new(&mut **write), cx),
+        }
+    }
+}
+
+/// A writer for the payload of a single text or binary message that sends the
+/// payload in frames as it is written, created by
+/// [`WebSocketStream::send_streaming`] or [`WriteHalf::send_streaming`].
+///
+/// The writer implements [`AsyncWrite`]. Written data is buffered until a full
+/// frame of [`Config::frame_size`] bytes is available and more data is written,
+/// or the writer is flushed, at which point a frame is queued for sending.
+/// Shutting down the writer sends the remaining data as the final frame of the
+/// message, but does not close the stream. The payload of text messages is
+/// validated to be UTF-8 as it is written and shutting down fails if it ends
+/// with an incomplete character.
+///
+/// Control frames queued with [`MessageWriter::queue_control`] are sent in
+/// between the frames of the message. A writer created from a
+/// [`WebSocketStream`] borrows the whole stream, so pings of the peer are only
+/// answered once the stream is read from again. The [`ReadHalf`] of a split
+/// stream keeps reading while a [`WriteHalf`] writes a message, and its
+/// automatic replies to pings and close frames are sent in between the frames
+/// of the message.
+///
+/// No other data frames can be sent until the final frame of the message was
+/// queued.
+///
+/// Dropping the writer without shutting it down queues the final frame with
+/// all data written so far. If the payload of a text message is not valid
+/// UTF-8, the connection is closed with
+/// [`CloseCode::INVALID_FRAME_PAYLOAD_DATA`] instead.
+///
+/// [`CloseCode::INVALID_FRAME_PAYLOAD_DATA`]: crate::CloseCode::INVALID_FRAME_PAYLOAD_DATA
+/// [`Config::frame_size`]: crate::Config::frame_size
+/// [`ReadHalf`]: super::ReadHalf
+#[derive(Debug)]
+pub struct MessageWriter<'a, T> {
+    /// The stream or write half the message is written to.
+    target: Target<'a, T>,
+    /// The opcode of the next frame.
+    opcode: OpCode,
+    /// Data written that has not been queued as a frame yet.
+    buffer: BytesMut,
+    /// UTF-8 validator for text messages.
+    validator: Option<Validator>,
+    /// Whether the final frame of the message was queued.
+    finished: bool,
+    /// Whether the payload of a text message failed UTF-8 validation.
+    is_invalid: bool,
+}
+
+impl<'a, T> MessageWriter<'a, T> {
+    /// Creates a new writer for a message with the given opcode.
+    pub(super) fn new(target: Target<'a, T>, opcode: OpCode) -> Self {
+        Self {
+            target,
+            opcode,
+            buffer: BytesMut::new(),
+            validator: (opcode == OpCode::Text).then(Validator::new),
+            finished: false,
+            is_invalid: false,
+        }
+    }
+
+    /// Queues a control message for sending in between the frames of the
+    /// message. It will be sent when the writer is flushed next.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the stream was closed.
+    ///
+    /// # Panics
+    ///
+    /// If `message` is not a ping, pong or close message.
+    pub fn queue_control(&mut self, message: Message) -> Result<(), Error> {
+        assert!(
+            message.opcode.is_control(),
+            "only control messages can be sent while writing a message"
+        );
+
+        self.target.start_send_frame(message.into())
+    }
+
+    /// Queues the buffered data as a frame.
+    fn queue_frame(&mut self, is_final: bool) -> Result<(), Error> {
+        let frame = Frame {
+            opcode: replace(&mut self.opcode, OpCode::Continuation),
+            is_final,
+            rsv: Rsv::NONE,
+            payload: Payload::from(self.buffer.split()),
+        };
+        self.finished = is_final;
+
+        self.target.start_send_frame(frame)
+    }
+
+    /// Validates the UTF-8 of text messages written so far, remembering any
+    /// failure.
+    fn validate(&mut self, data: &[u8], is_complete: bool) -> Result<(), Error> {
+        if self.is_invalid {
+            return Err(Error::Protocol(ProtocolError::InvalidUtf8));
+        }
+
+        if let Some(validator) = &mut self.validator {
+            if let Err(e) = validator.feed(data, is_complete) {
+                self.is_invalid = true;
+
+                return Err(Error::Protocol(e));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> AsyncWrite for MessageWriter<'_, T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.finished {
+            return Poll::Ready(Err(Error::AlreadyClosed.into_io_error()));
+        }
+
+        ready!(self.target.poll_ready(cx)).map_err(Error::into_io_error)?;
+
+        let frame_size = self.target.frame_size();
+
+        // A full frame is only queued once more data follows, so that shutting down
+        // can mark the last frame as final without sending an empty one
+        if self.buffer.len() >= frame_size {
+            self.queue_frame(false).map_err(Error::into_io_error)?;
+        }
+
+        let n = buf.len().min(frame_size - self.buffer.len());
+        let data = &buf[..n];
+
+        self.validate(data, false).map_err(Error::into_io_error)?;
+        self.buffer.extend_from_slice(data);
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.finished && !self.buffer.is_empty() {
+            self.queue_frame(false).map_err(Error::into_io_error)?;
+        }
+
+        self.target.poll_flush(cx).map_err(Error::into_io_error)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.finished {
+            self.validate(&[], true).map_err(Error::into_io_error)?;
+            self.queue_frame(true).map_err(Error::into_io_error)?;
+        }
+
+        self.target.poll_flush(cx).map_err(Error::into_io_error)
+    }
+}
+
+impl<T> Drop for MessageWriter<'_, T> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        if self.validate(&[], true).is_ok() {
+            _ = self.queue_frame(true);
+        } else {
+            let close = Message::close(Some(CloseCode::INVALID_FRAME_PAYLOAD_DATA), "");
+            _ = self.target.start_send_frame(close.into());
+        }
+    }
+}
@@ -5,27 +5,451 @@
 //!     established stream, via [`Builder::accept`]
 //!   - By performing the handshake yourself and then using [`Builder::serve`]
 //!     to let it take over a WebSocket stream
-use std::{future::poll_fn, io, pin::Pin};
+//!
+//! Upgrade requests can be inspected, rejected or customized before accepting
+//! them with a [`HandshakeHandler`] passed to [`Builder::on_handshake`].
+use std::{
+    fmt,
+    future::{poll_fn, Future},
+    io,
+    pin::Pin,
+};
 
+use bytes::Bytes;
 use futures_core::Stream;
+use http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio_util::codec::FramedRead;
 
+#[cfg(feature = "permessage-deflate")]
+use crate::extensions::PerMessageDeflate;
 use crate::{
+    extensions::{self, Extension, ExtensionConfig},
     proto::{Config, Limits, Role},
-    upgrade::client_request,
+    upgrade::{self, client_request},
     Error, WebSocketStream,
 };
 
-/// HTTP/1.1 400 Bad Request response payload.
-const BAD_REQUEST: &[u8] = b"HTTP/1.1 400 Bad Request\r\n\r\n";
+/// A static HTTP/1.1 101 Switching Protocols response up until the
+/// `Sec-WebSocket-Accept` header value.
+const SWITCHING_PROTOCOLS_BODY: &[u8] = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: ";
+
+/// Headers of the 101 Switching Protocols response that are set by the server
+/// and not overridden by [`Accept::header`].
+const RESERVED_HEADERS: &[HeaderName] = &[
+    header::UPGRADE,
+    header::CONNECTION,
+    header::SEC_WEBSOCKET_ACCEPT,
+    header::SEC_WEBSOCKET_EXTENSIONS,
+    header::SEC_WEBSOCKET_PROTOCOL,
+];
+
+/// Writes HTTP headers in HTTP/1.1 format into a buffer.
+fn write_headers(buf: &mut Vec<u8>, headers: &HeaderMap) {
+    for (name, value) in headers {
+        buf.extend_from_slice(name.as_str().as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Builds a HTTP/1.1 101 Switching Protocols response with a
+/// `Sec-WebSocket-Accept` value, optional `Sec-WebSocket-Extensions` and
+/// `Sec-WebSocket-Protocol` values and extra headers.
+fn build_response(
+    ws_accept: &str,
+    extensions: Option<&str>,
+    subprotocol: Option<&str>,
+    headers: &HeaderMap,
+) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(SWITCHING_PROTOCOLS_BODY.len() + ws_accept.len() + 4);
+
+    buf.extend_from_slice(SWITCHING_PROTOCOLS_BODY);
+    buf.extend_from_slice(ws_accept.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+
+    if let Some(extensions) = extensions {
+        buf.extend_from_slice(b"Sec-WebSocket-Extensions: ");
+        buf.extend_from_slice(extensions.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    if let Some(subprotocol) = subprotocol {
+        buf.extend_from_slice(b"Sec-WebSocket-Protocol: ");
+        buf.extend_from_slice(subprotocol.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    write_headers(&mut buf, headers);
+    buf.extend_from_slice(b"\r\n");
+
+    buf
+}
+
+/// Serializes a response that rejects an upgrade request in HTTP/1.1 format.
+/// A `Content-Length` header is added unless the response has one.
+fn build_rejection(response: &http::Response<Bytes>) -> Vec<u8> {
+    let status = response.status();
+    let body = response.body();
+    let mut buf = Vec::with_capacity(128 + body.len());
+
+    buf.extend_from_slice(b"HTTP/1.1 ");
+    buf.extend_from_slice(status.as_str().as_bytes());
+    buf.extend_from_slice(b" ");
+    buf.extend_from_slice(status.canonical_reason().unwrap_or_default().as_bytes());
+    buf.extend_from_slice(b"\r\n");
+
+    write_headers(&mut buf, response.headers());
+
+    if !response.headers().contains_key(header::CONTENT_LENGTH) {
+        buf.extend_from_slice(b"content-length: ");
+        buf.extend_from_slice(body.len().to_string().as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    buf.extend_from_slice(b"\r\n");
+    buf.extend_from_slice(body);
+
+    buf
+}
+
+/// Replaces the status of a response that rejects an upgrade request with 500
+/// Internal Server Error unless it is a client or server error, so that a
+/// rejection can never look like a successful response to the client.
+fn ensure_error_status(mut response: http::Response<Bytes>) -> http::Response<Bytes> {
+    let status = response.status();
+
+    if !status.is_client_error() && !status.is_server_error() {
+        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    response
+}
+
+/// Writes a response that rejects an upgrade request and shuts down the
+/// stream. The response is sent with an error status as ensured by
+/// [`ensure_error_status`], which is returned.
+async fn send_rejection<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    response: http::Response<Bytes>,
+) -> Result<StatusCode, Error> {
+    let response = ensure_error_status(response);
+
+    stream.write_all(&build_rejection(&response)).await?;
+    stream.shutdown().await?;
+
+    Ok(response.status())
+}
+
+/// Builds the response that rejects an invalid upgrade request, with a short
+/// description of the error as the body if `details` is set.
+///
+/// Requests that are not WebSocket upgrades or use an unsupported version are
+/// answered with 426 Upgrade Required as required by RFC 6455, Section 4.4 and
+/// RFC 9110, Section 15.5.22.
+fn rejection_response(error: &upgrade::Error, details: bool) -> http::Response<Bytes> {
+    let status = match error {
+        upgrade::Error::MissingHeader("Sec-WebSocket-Key") => StatusCode::BAD_REQUEST,
+        upgrade::Error::MissingHeader(_)
+        | upgrade::Error::UpgradeNotWebSocket
+        | upgrade::Error::ConnectionNotUpgrade
+        | upgrade::Error::UnsupportedWebSocketVersion => StatusCode::UPGRADE_REQUIRED,
+        upgrade::Error::Parsing(httparse::Error::TooManyHeaders) => {
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        }
+        upgrade::Error::OriginNotAllowed | upgrade::Error::HostNotAllowed => StatusCode::FORBIDDEN,
+        upgrade::Error::Rejected(status) => {
+            StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_REQUEST)
+        }
+        upgrade::Error::Parsing(_)
+        | upgrade::Error::DidNotSwitchProtocols(_)
+        | upgrade::Error::WrongWebSocketAccept
+        | upgrade::Error::InvalidExtension
+        | upgrade::Error::InvalidSubprotocol => StatusCode::BAD_REQUEST,
+    };
+
+    let mut response = http::Response::new(Bytes::new());
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+
+    if status == StatusCode::UPGRADE_REQUIRED {
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.insert(
+            header::SEC_WEBSOCKET_VERSION,
+            HeaderValue::from_static("13"),
+        );
+    }
+
+    if details {
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        *response.body_mut() = Bytes::from(error.to_string());
+    }
+
+    response
+}
+
+/// Decision of a [`HandshakeHandler`] to accept an upgrade request, with
+/// extra response headers and settings for the connection.
+#[derive(Debug, Default)]
+pub struct Accept {
+    /// Extra headers to send in the 101 Switching Protocols response.
+    headers: HeaderMap,
+    /// Configuration for the connection instead of the one of the builder.
+    config: Option<Config>,
+    /// Limits for the connection instead of the ones of the builder.
+    limits: Option<Limits>,
+}
+
+impl Accept {
+    /// Creates a decision to accept the request with the settings of the
+    /// [`Builder`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a header to the 101 Switching Protocols response, such as
+    /// `Set-Cookie`. Headers that the server sets for the WebSocket handshake
+    /// itself, such as `Sec-WebSocket-Accept` or `Sec-WebSocket-Protocol`,
+    /// are ignored.
+    #[must_use]
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        if !RESERVED_HEADERS.contains(&name) {
+            self.headers.append(name, value);
+        }
+
+        self
+    }
+
+    /// Sets the configuration for this connection instead of the one of the
+    /// [`Builder`].
+    #[must_use]
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+
+        self
+    }
+
+    /// Sets the limits for this connection instead of the ones of the
+    /// [`Builder`].
+    #[must_use]
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+
+        self
+    }
+}
+
+/// Trait for inspecting valid upgrade requests before the server accepts them,
+/// for example to check authentication, set cookies or pick settings per
+/// client.
+///
+/// It is implemented for closures that take the request and return a future,
+/// which cannot borrow the request:
+///
+/// ```
+/// use bytes::Bytes;
+/// use http::{header, Response, StatusCode};
+/// use tokio_websockets::{server::Accept, ServerBuilder};
+///
+/// let builder = ServerBuilder::new().on_handshake(|request: &http::Request<()>| {
+///     let authorized = request.headers().contains_key(header::AUTHORIZATION);
+///
+///     async move {
+///         if authorized {
+///             Ok(Accept::new())
+///         } else {
+///             let mut response = Response::new(Bytes::new());
+///             *response.status_mut() = StatusCode::UNAUTHORIZED;
+///             Err(response)
+///         }
+///     }
+/// });
+/// ```
+pub trait HandshakeHandler: Send + Sync {
+    /// Decides whether to accept an upgrade request. Returning a response
+    /// rejects the request, the response is written to the client before the
+    /// connection is shut down. Responses without a 4xx or 5xx status are sent
+    /// with 500 Internal Server Error instead.
+    fn handshake(
+        &self,
+        request: &http::Request<()>,
+    ) -> impl Future<Output = Result<Accept, http::Response<Bytes>>> + Send;
+}
+
+/// A [`HandshakeHandler`] that accepts all valid upgrade requests with the
+/// settings of the [`Builder`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AcceptAll;
+
+impl HandshakeHandler for AcceptAll {
+    async fn handshake(&self, _: &http::Request<()>) -> Result<Accept, http::Response<Bytes>> {
+        Ok(Accept::new())
+    }
+}
+
+impl<F, Fut> HandshakeHandler for F
+where
+    F: Fn(&http::Request<()>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Accept, http::Response<Bytes>>> + Send,
+{
+    fn handshake(
+        &self,
+        request: &http::Request<()>,
+    ) -> impl Future<Output = Result<Accept, http::Response<Bytes>>> + Send {
+        self(request)
+    }
+}
+
+/// Inner representation of a [`Matcher`].
+enum MatcherKind {
+    /// Matches one value, ignoring ASCII case.
+    Exact(String),
+    /// Matches values that start with the prefix and end with a subdomain of
+    /// the suffix, ignoring ASCII case.
+    Subdomain {
+        /// Part of the pattern before the wildcard, such as a scheme.
+        prefix: String,
+        /// Part of the pattern after the wildcard, starting with a dot.
+        suffix: String,
+    },
+    /// Matches values that the function returns `true` for.
+    Predicate(Box<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+/// A pattern for values of the `Origin` and `Host` headers that are allowed
+/// by [`Builder::allowed_origins`] and [`Builder::allowed_hosts`].
+pub struct Matcher(MatcherKind);
+
+impl Matcher {
+    /// Matches exactly the given value, ignoring ASCII case, such as
+    /// `https://example.com` for origins or `example.com` for hosts.
+    #[must_use]
+    pub fn exact(value: impl Into<String>) -> Self {
+        Self(MatcherKind::Exact(value.into()))
+    }
+
+    /// Matches subdomains of any depth in place of a `*.` wildcard in the
+    /// pattern, ignoring ASCII case. For example, `https://*.example.com`
+    /// matches `https://api.example.com` and `https://a.b.example.com`, but
+    /// not `https://example.com`.
+    ///
+    /// # Panics
+    ///
+    /// If the pattern does not contain `*.`.
+    #[must_use]
+    pub fn subdomains(pattern: &str) -> Self {
+        let (prefix, suffix) = pattern
+            .split_once("*.")
+            .expect("subdomain pattern must contain a *. wildcard");
+
+        Self(MatcherKind::Subdomain {
+            prefix: prefix.to_owned(),
+            suffix: format!(".{suffix}"),
+        })
+    }
+
+    /// Matches values that the given function returns `true` for.
+    #[must_use]
+    pub fn predicate<F>(predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self(MatcherKind::Predicate(Box::new(predicate)))
+    }
+
+    /// Returns whether the value matches the pattern.
+    fn matches(&self, value: &str) -> bool {
+        match &self.0 {
+            MatcherKind::Exact(expected) => value.eq_ignore_ascii_case(expected),
+            MatcherKind::Subdomain { prefix, suffix } => {
+                let Some(subdomain) = strip_prefix_ignore_ascii_case(value, prefix)
+                    .and_then(|rest| strip_suffix_ignore_ascii_case(rest, suffix))
+                else {
+                    return false;
+                };
+
+                !subdomain.is_empty()
+                    && subdomain
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.')
+            }
+            MatcherKind::Predicate(predicate) => predicate(value),
+        }
+    }
+}
+
+impl fmt::Debug for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            MatcherKind::Exact(value) => f.debug_tuple("Exact").field(value).finish(),
+            MatcherKind::Subdomain { prefix, suffix } => f
+                .debug_struct("Subdomain")
+                .field("prefix", prefix)
+                .field("suffix", suffix)
+                .finish(),
+            MatcherKind::Predicate(_) => f.write_str("Predicate"),
+        }
+    }
+}
+
+/// Removes a prefix from a string, ignoring ASCII case.
+fn strip_prefix_ignore_ascii_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = value.get(..prefix.len())?;
+
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &value[prefix.len()..])
+}
+
+/// Removes a suffix from a string, ignoring ASCII case.
+fn strip_suffix_ignore_ascii_case<'a>(value: &'a str, suffix: &str) -> Option<&'a str> {
+    let start = value.len().checked_sub(suffix.len())?;
+    let tail = value.get(start..)?;
+
+    tail.eq_ignore_ascii_case(suffix).then(|| &value[..start])
+}
+
+/// Removes the port from a `Host` header value.
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        // IPv6 addresses are enclosed in brackets
+        host.find(']').map_or(host, |end| &host[..=end])
+    } else {
+        host.split_once(':').map_or(host, |(host, _)| host)
+    }
+}
+
+/// Function that builds a custom response to an invalid upgrade request, or
+/// returns `None` to use the default one.
+type RejectionTemplate =
+    Box<dyn Fn(&upgrade::Error) -> Option<http::Response<Bytes>> + Send + Sync>;
 
 /// Builder for WebSocket server connections.
-pub struct Builder {
+pub struct Builder<H: HandshakeHandler = AcceptAll> {
     /// Configuration for the WebSocket stream.
     config: Config,
     /// Limits to impose on the WebSocket stream.
     limits: Limits,
+    /// Extensions to accept if offered by the client.
+    extensions: ExtensionConfig,
+    /// Subprotocols to select from those offered by the client, in order of
+    /// preference.
+    subprotocols: Vec<String>,
+    /// Origins that upgrade requests may come from, if restricted.
+    allowed_origins: Option<Vec<Matcher>>,
+    /// Hosts that upgrade requests may be sent to, if restricted.
+    allowed_hosts: Option<Vec<Matcher>>,
+    /// Whether responses to invalid upgrade requests describe the error.
+    rejection_details: bool,
+    /// Function that builds custom responses to invalid upgrade requests.
+    rejection_template: Option<RejectionTemplate>,
+    /// Handler that decides whether to accept upgrade requests.
+    handler: H,
 }
 
 impl Default for Builder {
@@ -42,9 +466,18 @@ impl Builder {
         Self {
             config: Config::default(),
             limits: Limits::default(),
+            extensions: ExtensionConfig::default(),
+            subprotocols: Vec::new(),
+            allowed_origins: None,
+            allowed_hosts: None,
+            rejection_details: false,
+            rejection_template: None,
+            handler: AcceptAll,
         }
     }
+}
 
+impl<H: HandshakeHandler> Builder<H> {
     /// Sets the configuration for the WebSocket stream.
     #[must_use]
     pub fn config(mut self, config: Config) -> Self {
@@ -61,12 +494,236 @@ impl Builder {
         self
     }
 
+    /// Adds an extension to accept if the client offers it. Extensions are
+    /// accepted in the order they were added in.
+    #[must_use]
+    pub fn extension<E: Extension + 'static>(mut self, extension: E) -> Self {
+        self.extensions.push(Box::new(extension));
+
+        self
+    }
+
+    /// Enables the permessage-deflate extension with the given configuration
+    /// if the client offers it. This is a shorthand for [`Builder::extension`].
+    #[cfg(feature = "permessage-deflate")]
+    #[must_use]
+    pub fn permessage_deflate(self, config: PerMessageDeflate) -> Self {
+        self.extension(config)
+    }
+
+    /// Sets the subprotocols supported by the server, in order of preference.
+    /// The first of them that the client offers in the
+    /// `Sec-WebSocket-Protocol` header is selected and sent back to the
+    /// client. If the client offers none of them, the handshake succeeds
+    /// without a subprotocol.
+    ///
+    /// The selected subprotocol is available via
+    /// [`WebSocketStream::subprotocol`].
+    ///
+    /// # Panics
+    ///
+    /// If any of the subprotocols is empty or contains characters that are not
+    /// allowed in HTTP tokens.
+    #[must_use]
+    pub fn subprotocols<I>(mut self, subprotocols: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.subprotocols = subprotocols.into_iter().map(Into::into).collect();
+        assert!(
+            self.subprotocols
+                .iter()
+                .all(|name| extensions::is_token(name)),
+            "subprotocols must be valid HTTP tokens"
+        );
+
+        self
+    }
+
+    /// Restricts the origins that upgrade requests may come from to those
+    /// matching any of the given patterns. This prevents websites on other
+    /// origins from connecting on behalf of a user with their cookies, known as
+    /// cross-site WebSocket hijacking.
+    ///
+    /// Requests with an `Origin` header that matches none of the patterns or
+    /// with more than one `Origin` header are rejected with 403 Forbidden and
+    /// [`upgrade::Error::OriginNotAllowed`].
+    /// Requests without an `Origin` header are allowed, since browsers always
+    /// send it.
+    #[must_use]
+    pub fn allowed_origins<I: IntoIterator<Item = Matcher>>(mut self, origins: I) -> Self {
+        self.allowed_origins = Some(origins.into_iter().collect());
+
+        self
+    }
+
+    /// Restricts the hosts that upgrade requests may be sent to to those
+    /// matching any of the given patterns. The port of the `Host` header is
+    /// ignored when matching.
+    ///
+    /// Requests with a missing `Host` header, more than one `Host` header or
+    /// one that matches none of the patterns are rejected with 403 Forbidden
+    /// and [`upgrade::Error::HostNotAllowed`].
+    #[must_use]
+    pub fn allowed_hosts<I: IntoIterator<Item = Matcher>>(mut self, hosts: I) -> Self {
+        self.allowed_hosts = Some(hosts.into_iter().collect());
+
+        self
+    }
+
+    /// Sets whether responses to invalid upgrade requests include a short
+    /// plain text description of the error as the body. This is disabled by
+    /// default.
+    #[must_use]
+    pub fn rejection_details(mut self, rejection_details: bool) -> Self {
+        self.rejection_details = rejection_details;
+
+        self
+    }
+
+    /// Sets a function that builds the response to invalid upgrade requests
+    /// for each kind of [`upgrade::Error`]. If it returns `None`, the default
+    /// response is sent instead. Responses without a 4xx or 5xx status are
+    /// sent with 500 Internal Server Error.
+    ///
+    /// By default, requests that are not WebSocket upgrades or use an
+    /// unsupported WebSocket version are answered with 426 Upgrade Required
+    /// and a `Sec-WebSocket-Version: 13` header, requests that violate
+    /// [`Builder::allowed_origins`] or [`Builder::allowed_hosts`] with 403
+    /// Forbidden and other invalid requests with 400 Bad Request.
+    #[must_use]
+    pub fn rejection_response<F>(mut self, template: F) -> Self
+    where
+        F: Fn(&upgrade::Error) -> Option<http::Response<Bytes>> + Send + Sync + 'static,
+    {
+        self.rejection_template = Some(Box::new(template));
+
+        self
+    }
+
+    /// Writes the response to an invalid upgrade request and shuts down the
+    /// stream.
+    async fn reject<S: AsyncWrite + Unpin>(
+        &self,
+        stream: &mut S,
+        error: &upgrade::Error,
+    ) -> Result<(), Error> {
+        let response = self
+            .rejection_template
+            .as_ref()
+            .and_then(|template| template(error))
+            .unwrap_or_else(|| rejection_response(error, self.rejection_details));
+
+        send_rejection(stream, response).await?;
+
+        Ok(())
+    }
+
+    /// Checks the `Origin` and `Host` headers of a request against the
+    /// allowed origins and hosts. Requests with more than one value for a
+    /// checked header are rejected, since it is ambiguous which one applies.
+    fn check_policy(&self, request: &http::Request<()>) -> Result<(), upgrade::Error> {
+        let is_allowed = |matchers: &[Matcher], value: &str| {
+            matchers.iter().any(|matcher| matcher.matches(value))
+        };
+
+        if let Some(origins) = &self.allowed_origins {
+            let mut values = request.headers().get_all(header::ORIGIN).iter();
+
+            match (values.next(), values.next()) {
+                (Some(origin), None) => {
+                    if !origin
+                        .to_str()
+                        .is_ok_and(|origin| is_allowed(origins, origin))
+                    {
+                        return Err(upgrade::Error::OriginNotAllowed);
+                    }
+                }
+                (None, _) => {}
+                (Some(_), Some(_)) => return Err(upgrade::Error::OriginNotAllowed),
+            }
+        }
+
+        if let Some(hosts) = &self.allowed_hosts {
+            let mut values = request.headers().get_all(header::HOST).iter();
+            let host = match (values.next(), values.next()) {
+                (Some(host), None) => host.to_str().ok(),
+                _ => None,
+            };
+
+            if !host.is_some_and(|host| is_allowed(hosts, strip_port(host))) {
+                return Err(upgrade::Error::HostNotAllowed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Selects the first supported subprotocol that the client offered.
+    fn select_subprotocol(&self, request: &http::Request<()>) -> Option<&str> {
+        if self.subprotocols.is_empty() {
+            return None;
+        }
+
+        let offered: Vec<&str> = upgrade::parse_subprotocols(
+            request
+                .headers()
+                .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+                .iter(),
+        )
+        .collect();
+
+        self.subprotocols
+            .iter()
+            .map(String::as_str)
+            .find(|name| offered.contains(name))
+    }
+
+    /// Sets the handler that inspects valid upgrade requests and decides
+    /// whether to accept them, see [`HandshakeHandler`].
+    ///
+    /// By default, all valid requests are accepted.
+    #[must_use]
+    pub fn on_handshake<NewH: HandshakeHandler>(self, handler: NewH) -> Builder<NewH> {
+        let Builder {
+            config,
+            limits,
+            extensions,
+            subprotocols,
+            allowed_origins,
+            allowed_hosts,
+            rejection_details,
+            rejection_template,
+            handler: _,
+        } = self;
+
+        Builder {
+            config,
+            limits,
+            extensions,
+            subprotocols,
+            allowed_origins,
+            allowed_hosts,
+            rejection_details,
+            rejection_template,
+            handler,
+        }
+    }
+
     /// Perform a HTTP upgrade handshake on an already established stream and
     /// uses it to send and receive WebSocket messages.
     ///
+    /// Valid upgrade requests are passed to the handler set via
+    /// [`Builder::on_handshake`] before accepting them.
+    ///
     /// # Errors
     ///
-    /// This method returns an [`Error`] if the handshake fails.
+    /// This method returns an [`Error`] if the handshake fails. Invalid
+    /// requests are answered with an error response first, see
+    /// [`Builder::rejection_response`]. If the handler rejected the request,
+    /// this is [`upgrade::Error::Rejected`] with the status code of the
+    /// response it returned.
     pub async fn accept<S: AsyncRead + AsyncWrite + Unpin>(
         &self,
         stream: S,
@@ -75,15 +732,55 @@ impl Builder {
         let reply = poll_fn(|cx| Pin::new(&mut framed).poll_next(cx)).await;
 
         match reply {
-            Some(Ok((request, response))) => {
-                framed.get_mut().write_all(response.as_bytes()).await?;
+            Some(Ok((request, ws_accept))) => {
+                if let Err(e) = self.check_policy(&request) {
+                    self.reject(framed.get_mut(), &e).await?;
+
+                    return Err(Error::Upgrade(e));
+                }
+
+                let accept = match self.handler.handshake(&request).await {
+                    Ok(accept) => accept,
+                    Err(response) => {
+                        let status = send_rejection(framed.get_mut(), response).await?;
+
+                        return Err(Error::Upgrade(upgrade::Error::Rejected(status.as_u16())));
+                    }
+                };
+
+                let (extensions_header, extensions) = self.extensions.accept(
+                    request
+                        .headers()
+                        .get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+                        .iter()
+                        .map(HeaderValue::as_bytes),
+                );
+                let subprotocol = self.select_subprotocol(&request);
+                let response = build_response(
+                    &ws_accept,
+                    extensions_header.as_deref(),
+                    subprotocol,
+                    &accept.headers,
+                );
+                framed.get_mut().write_all(&response).await?;
+
                 Ok((
                     request,
-                    WebSocketStream::from_framed(framed, Role::Server, self.config, self.limits),
+                    WebSocketStream::from_framed(
+                        framed,
+                        Role::Server,
+                        accept.config.unwrap_or(self.config),
+                        accept.limits.unwrap_or(self.limits),
+                        extensions,
+                        subprotocol.map(str::to_owned),
+                    ),
                 ))
             }
             Some(Err(e)) => {
-                framed.get_mut().write_all(BAD_REQUEST).await?;
+                // I/O errors leave nobody to respond to
+                if let Error::Upgrade(error) = &e {
+                    self.reject(framed.get_mut(), error).await?;
+                }
 
                 Err(e)
             }
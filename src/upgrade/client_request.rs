@@ -8,10 +8,6 @@ use tokio_util::codec::Decoder;
 
 use crate::{sha::digest, upgrade::Error};
 
-/// A static HTTP/1.1 101 Switching Protocols response up until the
-/// `Sec-WebSocket-Accept` header value.
-const SWITCHING_PROTOCOLS_BODY: &str = "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: ";
-
 /// Returns whether an ASCII byte slice is contained in another one, ignoring
 /// captalization.
 fn contains_ignore_ascii_case(mut haystack: &[u8], needle: &[u8]) -> bool {
@@ -90,7 +86,7 @@ impl ClientRequest {
 }
 
 /// A codec that implements a [`Decoder`] for HTTP/1.1 upgrade requests and
-/// yields the request and a HTTP/1.1 response to reply with.
+/// yields the request and the `Sec-WebSocket-Accept` value to reply with.
 ///
 /// It does not implement an [`Encoder`].
 ///
@@ -137,7 +133,7 @@ impl Decoder for Codec {
             let value = http::HeaderValue::from_bytes(header.value)
                 .map_err(|_| Error::Parsing(httparse::Error::HeaderValue))?;
 
-            header_map.insert(name, value);
+            header_map.append(name, value);
         }
 
         // You have to build the request before you can assign headers: https://github.com/hyperium/http/issues/91
@@ -152,12 +148,6 @@ impl Decoder for Codec {
 
         src.advance(request_len);
 
-        let mut resp = String::with_capacity(SWITCHING_PROTOCOLS_BODY.len() + ws_accept.len() + 4);
-
-        resp.push_str(SWITCHING_PROTOCOLS_BODY);
-        resp.push_str(&ws_accept);
-        resp.push_str("\r\n\r\n");
-
-        Ok(Some((request, resp)))
+        Ok(Some((request, ws_accept)))
     }
 }
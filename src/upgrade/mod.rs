@@ -32,6 +32,20 @@ pub enum Error {
     /// Server returned a `Sec-WebSocket-Accept` that is not compatible with the
     /// `Sec-WebSocket-Key` sent by the client.
     WrongWebSocketAccept,
+    /// Server accepted an extension in the `Sec-WebSocket-Extensions` header
+    /// that was not offered by the client or with invalid parameters.
+    InvalidExtension,
+    /// Server selected a subprotocol in the `Sec-WebSocket-Protocol` header
+    /// that was not offered by the client.
+    InvalidSubprotocol,
+    /// The server rejected the upgrade request with a response of the given
+    /// status code.
+    Rejected(u16),
+    /// The `Origin` header sent by the client is not allowed by the server.
+    OriginNotAllowed,
+    /// The `Host` header sent by the client is missing or not allowed by the
+    /// server.
+    HostNotAllowed,
 }
 
 impl fmt::Display for Error {
@@ -50,6 +64,14 @@ impl fmt::Display for Error {
                 f.write_fmt(format_args!("{status}"))
             }
             Error::WrongWebSocketAccept => f.write_str("mismatching Sec-WebSocket-Accept header"),
+            Error::InvalidExtension => f.write_str("invalid Sec-WebSocket-Extensions header"),
+            Error::InvalidSubprotocol => f.write_str("invalid Sec-WebSocket-Protocol header"),
+            Error::Rejected(status) => {
+                f.write_str("upgrade request rejected with status code ")?;
+                f.write_fmt(format_args!("{status}"))
+            }
+            Error::OriginNotAllowed => f.write_str("origin header value is not allowed"),
+            Error::HostNotAllowed => f.write_str("host header value is missing or not allowed"),
         }
     }
 }
@@ -62,12 +84,30 @@ impl std::error::Error for Error {
             | Error::ConnectionNotUpgrade
             | Error::UnsupportedWebSocketVersion
             | Error::DidNotSwitchProtocols(_)
-            | Error::WrongWebSocketAccept => None,
+            | Error::WrongWebSocketAccept
+            | Error::InvalidExtension
+            | Error::InvalidSubprotocol
+            | Error::Rejected(_)
+            | Error::OriginNotAllowed
+            | Error::HostNotAllowed => None,
             Error::Parsing(e) => Some(e),
         }
     }
 }
 
+/// Returns an iterator over the subprotocols listed in the values of
+/// `Sec-WebSocket-Protocol` headers.
+pub(crate) fn parse_subprotocols<'a, I>(values: I) -> impl Iterator<Item = &'a str>
+where
+    I: Iterator<Item = &'a http::HeaderValue>,
+{
+    values
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
 impl From<httparse::Error> for Error {
     fn from(err: httparse::Error) -> Self {
         Self::Parsing(err)
@@ -96,7 +96,7 @@ impl Decoder for Codec {
             let value = HeaderValue::from_bytes(header.value)
                 .map_err(|_| Error::Parsing(httparse::Error::HeaderValue))?;
 
-            header_map.insert(name, value);
+            header_map.append(name, value);
         }
 
         src.advance(response_len);
@@ -0,0 +1,46 @@
+#![cfg(all(feature = "client", feature = "server"))]
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::duplex;
+use tokio_websockets::{ClientBuilder, Message, ServerBuilder};
+
+#[tokio::test]
+async fn test_shared_payloads_are_masked() {
+    // A small pipe forces many partial writes of the masked payloads
+    let (one, two) = duplex(1000);
+    let client = ClientBuilder::new().uri("ws://localhost/").unwrap();
+    let server = ServerBuilder::new();
+
+    let (client, server) = tokio::join!(client.connect_on(one), server.accept(two));
+    let (mut client, _) = client.unwrap();
+    let (_, mut server) = server.unwrap();
+
+    let large: Bytes = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>().into();
+    let messages = [
+        Message::binary(Bytes::from_static(b"static payload")),
+        Message::binary(large.clone()),
+        Message::text("hello"),
+    ];
+
+    let send = async {
+        for message in messages {
+            client.feed(message).await.unwrap();
+        }
+        client.flush().await.unwrap();
+    };
+    let receive = async {
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(server.next().await.unwrap().unwrap());
+        }
+        received
+    };
+    let ((), received) = tokio::join!(send, receive);
+
+    assert_eq!(&received[0].as_payload()[..], b"static payload");
+    assert_eq!(&received[1].as_payload()[..], &large[..]);
+    assert_eq!(received[2].as_text(), Some("hello"));
+
+    // The payload that was sent is still shared and not masked
+    assert_eq!(large[1], 1);
+}
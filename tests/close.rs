@@ -0,0 +1,89 @@
+#![cfg(feature = "server")]
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{proto::ProtocolError, CloseCode, CloseFrame, Message, ServerBuilder};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+#[test]
+fn test_close_frame_new() {
+    let reason = format!("{}é", "a".repeat(122));
+    let frame = CloseFrame::new(CloseCode::GOING_AWAY, &reason).unwrap();
+    assert_eq!(frame.code(), CloseCode::GOING_AWAY);
+    assert_eq!(frame.reason(), "a".repeat(122));
+
+    assert!(matches!(
+        CloseFrame::new(CloseCode::NO_STATUS_RECEIVED, ""),
+        Err(ProtocolError::InvalidCloseCode)
+    ));
+}
+
+#[tokio::test]
+async fn test_close_with() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(&encode_frame(8, b"\x03\xe8", true))
+        .await
+        .unwrap();
+
+    let frame = CloseFrame::new(CloseCode::GOING_AWAY, "bye").unwrap();
+    server.close_with(frame).await.unwrap();
+
+    let mut buf = Vec::new();
+    drop(server);
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x88\x05\x03\xe9bye");
+}
+
+#[tokio::test]
+async fn test_peer_close_frame() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    assert!(server.peer_close_frame().is_none());
+
+    two.write_all(&encode_frame(8, b"\x03\xe9away", true))
+        .await
+        .unwrap();
+
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert!(server.next().await.is_none());
+
+    let frame = server.peer_close_frame().unwrap();
+    assert_eq!(frame.code(), CloseCode::GOING_AWAY);
+    assert_eq!(frame.reason(), "away");
+}
+
+#[tokio::test]
+async fn test_echo_empty_close_frame() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(&encode_frame(8, b"", true)).await.unwrap();
+
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert!(server.next().await.is_none());
+
+    // The code of an empty close frame may not be sent back to the peer
+    let frame = server.peer_close_frame().unwrap();
+    assert_eq!(frame.code(), CloseCode::NO_STATUS_RECEIVED);
+    let message = Message::from(frame.clone());
+    assert!(message.is_close());
+    assert!(message.as_payload().is_empty());
+
+    let mut buf = Vec::new();
+    drop(server);
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x88\x00");
+}
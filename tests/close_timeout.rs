@@ -0,0 +1,109 @@
+#![cfg(feature = "server")]
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    io::{duplex, AsyncReadExt, AsyncWriteExt},
+    time::Instant,
+};
+use tokio_websockets::{Config, Error, Message, ServerBuilder};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+fn config() -> Config {
+    Config::default().close_timeout(Some(Duration::from_secs(5)))
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_close_timeout() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().config(config()).serve(one);
+
+    let start = Instant::now();
+    assert!(matches!(server.close().await, Err(Error::CloseTimeout)));
+    assert_eq!(start.elapsed(), Duration::from_secs(5));
+
+    // The close frame was sent and the I/O was shut down afterwards
+    let mut buf = Vec::new();
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x88\x02\x03\xe8");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_close_timeout_while_reading() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().config(config()).serve(one);
+
+    server.send(Message::close(None, "")).await.unwrap();
+    two.write_all(&encode_frame(1, b"late", true))
+        .await
+        .unwrap();
+
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("late"));
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::CloseTimeout))
+    ));
+    assert!(server.next().await.is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_close_acknowledged_in_time() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().config(config()).serve(one);
+
+    let (result, _) = tokio::join!(server.close(), async {
+        let mut buf = [0; 4];
+        two.read_exact(&mut buf).await.unwrap();
+
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        two.write_all(&encode_frame(8, &buf[2..], true))
+            .await
+            .unwrap();
+    });
+
+    result.unwrap();
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(server.next().await.is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_close_timeout_split() {
+    let (one, _two) = duplex(usize::MAX);
+    let server = ServerBuilder::new().config(config()).serve(one);
+    let (mut read, mut write) = server.split();
+
+    let (next, close) = tokio::join!(read.next(), write.close());
+
+    close.unwrap();
+    assert!(matches!(next, Some(Err(Error::CloseTimeout))));
+    assert!(read.next().await.is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_close_timeout_split_with_buffered_close() {
+    let (one, mut two) = duplex(64);
+    let server = ServerBuilder::new().config(config()).serve(one);
+    let (mut read, mut write) = server.split();
+
+    // The peer closes, but the read half does not notice before the write half
+    // timed out, since the peer never reads what the write half sends
+    two.write_all(&encode_frame(8, b"\x03\xe8", true))
+        .await
+        .unwrap();
+    write.feed(Message::binary(vec![0; 128])).await.unwrap();
+    assert!(matches!(write.close().await, Err(Error::CloseTimeout)));
+
+    assert!(read.next().await.is_none());
+}
@@ -0,0 +1,19 @@
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{server::HandshakeHandler, Error, ServerBuilder};
+
+/// Sends a raw request to the server and returns the result of the handshake
+/// and the response of the server.
+pub async fn handshake<H: HandshakeHandler>(
+    server: &ServerBuilder<H>,
+    request: &str,
+) -> (Result<(), Error>, String) {
+    let (one, mut two) = duplex(usize::MAX);
+    two.write_all(request.as_bytes()).await.unwrap();
+
+    let result = server.accept(one).await.map(drop);
+
+    let mut response = String::new();
+    two.read_to_string(&mut response).await.unwrap();
+
+    (result, response)
+}
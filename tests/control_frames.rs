@@ -0,0 +1,138 @@
+#![cfg(feature = "server")]
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    io::{duplex, AsyncReadExt, AsyncWriteExt},
+    time::timeout,
+};
+use tokio_websockets::{Config, ServerBuilder};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+#[tokio::test]
+async fn test_pongs_are_coalesced() {
+    // The server can only write two bytes at a time while the peer is not
+    // reading, so its pongs pile up
+    let (one, mut two) = duplex(2);
+    let mut server = ServerBuilder::new().serve(one);
+
+    let (mut read, mut write) = tokio::io::split(&mut two);
+    let writer = async {
+        for payload in [b"a", b"b", b"c", b"d"] {
+            write
+                .write_all(&encode_frame(9, payload, true))
+                .await
+                .unwrap();
+        }
+    };
+    let reader = async {
+        for _ in 0..4 {
+            assert!(server.next().await.unwrap().unwrap().is_ping());
+        }
+    };
+    tokio::join!(writer, reader);
+
+    let mut buf = [0; 6];
+    let (flushed, read) = tokio::join!(server.flush(), read.read_exact(&mut buf));
+    flushed.unwrap();
+    read.unwrap();
+
+    // The first pong was already being written, the others were replaced by
+    // the reply to the latest ping
+    assert_eq!(buf, *b"\x8a\x01a\x8a\x01d");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_max_pending_bytes() {
+    let (one, two) = duplex(2);
+    let mut server = ServerBuilder::new()
+        .config(Config::default().max_pending_bytes(Some(0)))
+        .serve(one);
+
+    let (mut read, mut write) = tokio::io::split(two);
+    tokio::spawn(async move {
+        write
+            .write_all(&encode_frame(9, b"ping", true))
+            .await
+            .unwrap();
+        write
+            .write_all(&encode_frame(2, b"data", true))
+            .await
+            .unwrap();
+    });
+
+    assert!(server.next().await.unwrap().unwrap().is_ping());
+
+    // The pong cannot be written, so the server does not read any further
+    assert!(timeout(Duration::from_secs(1), server.next())
+        .await
+        .is_err());
+
+    let mut buf = [0; 6];
+    let (message, read) = tokio::join!(server.next(), read.read_exact(&mut buf));
+    read.unwrap();
+    assert_eq!(buf, *b"\x8a\x04ping");
+    assert_eq!(&*message.unwrap().unwrap().into_payload(), b"data");
+}
+
+#[tokio::test]
+async fn test_auto_pong_disabled() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .config(Config::default().auto_pong(false))
+        .serve(one);
+
+    two.write_all(&encode_frame(9, b"ping", true))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(8, b"\x03\xe8", true))
+        .await
+        .unwrap();
+
+    assert!(server.next().await.unwrap().unwrap().is_ping());
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert!(server.next().await.is_none());
+
+    // Only the close frame was answered
+    let mut buf = Vec::new();
+    drop(server);
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x88\x02\x03\xe8");
+}
+
+#[tokio::test]
+async fn test_pings_and_pongs_not_yielded() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .config(Config::default().yield_pings_and_pongs(false))
+        .serve(one);
+
+    two.write_all(&encode_frame(9, b"ping", true))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(10, b"pong", true))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(1, b"text", true))
+        .await
+        .unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("text"));
+
+    // The ping was still answered
+    let mut buf = [0; 6];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x8a\x04ping");
+}
@@ -0,0 +1,49 @@
+#![cfg(all(feature = "client", feature = "server"))]
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{duplex, AsyncReadExt};
+use tokio_websockets::{ClientBuilder, Config, Message, ServerBuilder};
+
+#[test]
+fn test_prepare_fragments() {
+    let config = Config::default().frame_size(4);
+
+    let encoded = Message::binary("abcdef").prepare(&config);
+    assert_eq!(encoded.as_bytes(), b"\x02\x04abcd\x80\x02ef");
+
+    // Control frames are never fragmented
+    let encoded = Message::ping("abcdef").prepare(&config);
+    assert_eq!(encoded.as_bytes(), b"\x89\x06abcdef");
+}
+
+#[tokio::test]
+async fn test_send_encoded_server() {
+    let config = Config::default().frame_size(4);
+    let encoded = Message::text("hello").prepare(&config);
+
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().config(config).serve(one);
+
+    server.send_encoded(&encoded).await.unwrap();
+    server.send(Message::text("hello")).await.unwrap();
+    drop(server);
+
+    // The shared frames match the frames the stream encodes itself
+    let mut buf = Vec::new();
+    two.read_to_end(&mut buf).await.unwrap();
+    let (shared, own) = buf.split_at(buf.len() / 2);
+    assert_eq!(shared, encoded.as_bytes());
+    assert_eq!(own, encoded.as_bytes());
+}
+
+#[tokio::test]
+async fn test_send_encoded_client() {
+    let (one, two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+    let mut client = ClientBuilder::new().take_over(two);
+
+    let encoded = Message::binary("hello").prepare(&Config::default());
+    client.send_encoded(&encoded).await.unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(&*message.into_payload(), b"hello");
+}
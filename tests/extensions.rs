@@ -0,0 +1,149 @@
+#![cfg(all(feature = "client", feature = "server"))]
+use futures_util::{SinkExt, StreamExt};
+use http::header::SEC_WEBSOCKET_EXTENSIONS;
+use tokio::io::duplex;
+use tokio_websockets::{
+    extensions::{Extension, NegotiatedExtension, Param},
+    proto::{Frame, Rsv},
+    ClientBuilder, Error, Message, ServerBuilder,
+};
+
+/// An extension that flips all bits of the payload of data frames and marks
+/// them with an RSV bit.
+struct Invert {
+    name: &'static str,
+    rsv: Rsv,
+}
+
+struct InvertCodec {
+    rsv: Rsv,
+}
+
+impl Extension for Invert {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn rsv(&self) -> Rsv {
+        self.rsv
+    }
+
+    fn offer(&self) -> Vec<Param> {
+        vec![Param::with_value("mode", "all")]
+    }
+
+    fn confirm(&self, params: &[Param]) -> Option<Box<dyn NegotiatedExtension>> {
+        (params.is_empty()).then(|| Box::new(InvertCodec { rsv: self.rsv }) as _)
+    }
+
+    fn accept(&self, offers: &[&[Param]]) -> Option<(Vec<Param>, Box<dyn NegotiatedExtension>)> {
+        offers
+            .iter()
+            .any(|params| params == &[Param::with_value("mode", "all")])
+            .then(|| (Vec::new(), Box::new(InvertCodec { rsv: self.rsv }) as _))
+    }
+}
+
+impl InvertCodec {
+    fn invert(frame: &mut Frame) {
+        let payload: Vec<u8> = frame.payload().iter().map(|b| !b).collect();
+        frame.set_payload(payload);
+    }
+}
+
+impl NegotiatedExtension for InvertCodec {
+    fn encode(&mut self, mut frame: Frame) -> Frame {
+        if !frame.opcode().is_control() {
+            Self::invert(&mut frame);
+            frame.set_rsv(frame.rsv() | self.rsv);
+        }
+
+        frame
+    }
+
+    fn decode(&mut self, mut frame: Frame, _max_len: usize) -> Result<Frame, Error> {
+        if frame.rsv().contains(self.rsv) {
+            Self::invert(&mut frame);
+            frame.set_rsv(frame.rsv().difference(self.rsv));
+        }
+
+        Ok(frame)
+    }
+}
+
+#[tokio::test]
+async fn test_custom_extensions() {
+    let (one, two) = duplex(usize::MAX);
+    let client = ClientBuilder::new()
+        .uri("ws://localhost/")
+        .unwrap()
+        .extension(Invert {
+            name: "x-invert",
+            rsv: Rsv::RSV2,
+        })
+        .extension(Invert {
+            name: "x-invert-again",
+            rsv: Rsv::RSV3,
+        });
+    let server = ServerBuilder::new()
+        .extension(Invert {
+            name: "x-invert-again",
+            rsv: Rsv::RSV3,
+        })
+        .extension(Invert {
+            name: "x-invert",
+            rsv: Rsv::RSV2,
+        });
+
+    let (client, server) = tokio::join!(client.connect_on(one), server.accept(two));
+    let (mut client, response) = client.unwrap();
+    let (request, mut server) = server.unwrap();
+
+    assert_eq!(
+        request.headers()[SEC_WEBSOCKET_EXTENSIONS],
+        "x-invert; mode=all, x-invert-again; mode=all"
+    );
+    assert_eq!(
+        response.headers()[SEC_WEBSOCKET_EXTENSIONS],
+        "x-invert-again, x-invert"
+    );
+
+    client.send(Message::text("Hello, world!")).await.unwrap();
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("Hello, world!"));
+
+    server.send(Message::binary(vec![1, 2, 3])).await.unwrap();
+    let msg = client.next().await.unwrap().unwrap();
+    assert_eq!(&**msg.as_payload(), &[1, 2, 3]);
+}
+
+#[tokio::test]
+async fn test_conflicting_rsv_not_accepted() {
+    let (one, two) = duplex(usize::MAX);
+    let client = ClientBuilder::new()
+        .uri("ws://localhost/")
+        .unwrap()
+        .extension(Invert {
+            name: "x-invert",
+            rsv: Rsv::RSV2,
+        })
+        .extension(Invert {
+            name: "x-invert-again",
+            rsv: Rsv::RSV2,
+        });
+    let server = ServerBuilder::new()
+        .extension(Invert {
+            name: "x-invert",
+            rsv: Rsv::RSV2,
+        })
+        .extension(Invert {
+            name: "x-invert-again",
+            rsv: Rsv::RSV2,
+        });
+
+    let (client, server) = tokio::join!(client.connect_on(one), server.accept(two));
+    let (_, response) = client.unwrap();
+    server.unwrap();
+
+    assert_eq!(response.headers()[SEC_WEBSOCKET_EXTENSIONS], "x-invert");
+}
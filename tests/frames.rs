@@ -0,0 +1,173 @@
+#![cfg(feature = "server")]
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{
+    proto::{Frame, OpCode, ProtocolError},
+    Error, ServerBuilder,
+};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+#[tokio::test]
+async fn test_frames_yield_fragments() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(&encode_frame(1, b"Hello, ", false))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(9, b"ping", true))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(0, b"world!", true))
+        .await
+        .unwrap();
+
+    let mut frames = server.frames();
+
+    let frame = frames.next().await.unwrap().unwrap();
+    assert_eq!(frame.opcode(), OpCode::Text);
+    assert!(!frame.is_final());
+    assert_eq!(&**frame.payload(), b"Hello, ");
+
+    let frame = frames.next().await.unwrap().unwrap();
+    assert_eq!(frame.opcode(), OpCode::Ping);
+
+    let frame = frames.next().await.unwrap().unwrap();
+    assert_eq!(frame.opcode(), OpCode::Continuation);
+    assert!(frame.is_final());
+    assert_eq!(&**frame.payload(), b"world!");
+
+    // The ping is answered automatically
+    frames.flush().await.unwrap();
+    let mut pong = [0; 6];
+    two.read_exact(&mut pong).await.unwrap();
+    assert_eq!(pong, *b"\x8a\x04ping");
+}
+
+#[tokio::test]
+async fn test_frames_validate_utf8() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(&encode_frame(1, b"Hello", false))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(0, &[0xff], true))
+        .await
+        .unwrap();
+
+    let mut frames = server.frames();
+
+    assert!(frames.next().await.unwrap().is_ok());
+    assert!(matches!(
+        frames.next().await,
+        Some(Err(Error::Protocol(ProtocolError::InvalidUtf8)))
+    ));
+}
+
+#[tokio::test]
+async fn test_frames_send() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+    let mut frames = server.frames();
+
+    frames
+        .feed(Frame::new(OpCode::Binary, &b"abc"[..], false))
+        .await
+        .unwrap();
+    frames
+        .send(Frame::new(OpCode::Continuation, &b"def"[..], true))
+        .await
+        .unwrap();
+
+    let mut buf = [0; 10];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x02\x03abc\x80\x03def");
+}
+
+#[tokio::test]
+async fn test_frames_send_rejects_invalid_frames() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+    let mut frames = server.frames();
+
+    let invalid = [
+        (
+            Frame::new(OpCode::Ping, vec![0; 126], true),
+            ProtocolError::InvalidPayloadLength,
+        ),
+        (
+            Frame::new(OpCode::Ping, &b"ping"[..], false),
+            ProtocolError::FragmentedControlFrame,
+        ),
+        (
+            Frame::new(OpCode::Close, &b"\x03"[..], true),
+            ProtocolError::InvalidPayloadLength,
+        ),
+        (
+            Frame::new(OpCode::Close, &b"\x03\xed"[..], true),
+            ProtocolError::InvalidCloseCode,
+        ),
+        (
+            Frame::new(OpCode::Close, &b"\x03\xe8\xff"[..], true),
+            ProtocolError::InvalidUtf8,
+        ),
+        (
+            Frame::new(OpCode::Text, &b"\xff"[..], true),
+            ProtocolError::InvalidUtf8,
+        ),
+        (
+            Frame::new(OpCode::Continuation, &b"abc"[..], true),
+            ProtocolError::InvalidOpcode,
+        ),
+    ];
+
+    for (frame, expected) in invalid {
+        let err = frames.feed(frame).await.unwrap_err();
+        assert!(
+            matches!(&err, Error::Protocol(e) if e.to_string() == expected.to_string()),
+            "expected {expected}, got {err}"
+        );
+    }
+
+    // Fragmented messages cannot be interrupted by other data frames and their
+    // text has to be valid UTF-8 across frames
+    frames
+        .feed(Frame::new(OpCode::Text, &b"\xc3"[..], false))
+        .await
+        .unwrap();
+    assert!(matches!(
+        frames
+            .feed(Frame::new(OpCode::Binary, &b"abc"[..], true))
+            .await,
+        Err(Error::Protocol(ProtocolError::InvalidOpcode))
+    ));
+    assert!(matches!(
+        frames
+            .feed(Frame::new(OpCode::Continuation, &b"a"[..], true))
+            .await,
+        Err(Error::Protocol(ProtocolError::InvalidUtf8))
+    ));
+    frames
+        .send(Frame::new(OpCode::Continuation, &b"\xa4"[..], true))
+        .await
+        .unwrap();
+    drop(server);
+
+    // Only the valid frames were sent
+    let mut buf = Vec::new();
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x01\x01\xc3\x80\x01\xa4");
+}
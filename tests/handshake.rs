@@ -0,0 +1,93 @@
+#![cfg(all(feature = "client", feature = "server"))]
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use http::{
+    header::{AUTHORIZATION, SET_COOKIE},
+    HeaderValue, Response, StatusCode,
+};
+use tokio::io::duplex;
+use tokio_websockets::{
+    server::Accept, upgrade, ClientBuilder, Error, Limits, Message, ServerBuilder,
+};
+
+use self::common::handshake;
+
+mod common;
+
+#[tokio::test]
+async fn test_handshake_accept() {
+    let (one, two) = duplex(usize::MAX);
+    let client = ClientBuilder::new()
+        .uri("ws://localhost/")
+        .unwrap()
+        .add_header(AUTHORIZATION, HeaderValue::from_static("token"))
+        .unwrap();
+    let server = ServerBuilder::new().on_handshake(|request: &http::Request<()>| {
+        let token = request.headers().get(AUTHORIZATION).cloned();
+
+        async move {
+            assert_eq!(token.unwrap(), "token");
+
+            Ok(Accept::new()
+                .header(SET_COOKIE, HeaderValue::from_static("session=1"))
+                .header(
+                    http::header::SEC_WEBSOCKET_PROTOCOL,
+                    HeaderValue::from_static("ignored"),
+                )
+                .limits(Limits::default().max_payload_len(Some(4))))
+        }
+    });
+
+    let (client, server) = tokio::join!(client.connect_on(one), server.accept(two));
+    let (mut client, response) = client.unwrap();
+    let (_, mut server) = server.unwrap();
+
+    assert_eq!(response.headers()[SET_COOKIE], "session=1");
+    assert!(!response
+        .headers()
+        .contains_key(http::header::SEC_WEBSOCKET_PROTOCOL));
+
+    // The limits of the handler apply instead of those of the builder
+    client.send(Message::binary("hello")).await.unwrap();
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::PayloadTooLong { len: 5, max_len: 4 }))
+    ));
+}
+
+/// A valid upgrade request.
+const UPGRADE_REQUEST: &str =
+    "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: \
+                               Upgrade\r\nSec-WebSocket-Key: \
+                               dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+
+#[tokio::test]
+async fn test_handshake_reject() {
+    let server = ServerBuilder::new().on_handshake(|_: &http::Request<()>| async {
+        let mut response = Response::new(Bytes::from_static(b"no token"));
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        Err(response)
+    });
+
+    let (result, response) = handshake(&server, UPGRADE_REQUEST).await;
+    assert!(matches!(
+        result,
+        Err(Error::Upgrade(upgrade::Error::Rejected(401)))
+    ));
+    assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+    assert!(response.ends_with("content-length: 8\r\n\r\nno token"));
+}
+
+#[tokio::test]
+async fn test_handshake_reject_without_error_status() {
+    let server = ServerBuilder::new()
+        .on_handshake(|_: &http::Request<()>| async { Err(Response::new(Bytes::new())) });
+
+    // A rejection is never sent with a successful status
+    let (result, response) = handshake(&server, UPGRADE_REQUEST).await;
+    assert!(matches!(
+        result,
+        Err(Error::Upgrade(upgrade::Error::Rejected(500)))
+    ));
+    assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+}
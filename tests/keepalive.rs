@@ -0,0 +1,85 @@
+#![cfg(feature = "server")]
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{Config, Error, ServerBuilder};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+fn config() -> Config {
+    Config::default()
+        .ping_interval(Some(Duration::from_secs(10)))
+        .pong_timeout(Some(Duration::from_secs(5)))
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keepalive_ping_and_rtt() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().config(config()).serve(one);
+
+    assert_eq!(server.rtt(), None);
+
+    let (msg, _) = tokio::join!(server.next(), async {
+        let mut ping = [0; 10];
+        two.read_exact(&mut ping).await.unwrap();
+        assert_eq!(ping[..2], *b"\x89\x08");
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        two.write_all(&encode_frame(10, &ping[2..], true))
+            .await
+            .unwrap();
+    });
+
+    assert!(msg.unwrap().unwrap().is_pong());
+    assert_eq!(server.rtt(), Some(Duration::from_millis(20)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keepalive_traffic_prevents_timeout() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().config(config()).serve(one);
+
+    let (msg, _) = tokio::join!(server.next(), async {
+        let mut ping = [0; 10];
+        two.read_exact(&mut ping).await.unwrap();
+
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        two.write_all(&encode_frame(1, b"alive", true))
+            .await
+            .unwrap();
+    });
+
+    assert_eq!(msg.unwrap().unwrap().as_text(), Some("alive"));
+    assert_eq!(server.rtt(), None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keepalive_timeout() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().config(config()).serve(one);
+
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::KeepaliveTimeout))
+    ));
+    assert!(server.next().await.is_none());
+
+    let mut buf = [0; 10];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf[..2], *b"\x89\x08");
+
+    let mut buf = [0; 21];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x88\x13\x03\xe9keepalive timeout");
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "server")]
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{Error, Limits, ServerBuilder};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+#[tokio::test]
+async fn test_max_frame_len() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .limits(Limits::default().max_frame_len(Some(4)))
+        .serve(one);
+
+    two.write_all(&encode_frame(2, b"abcd", false))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(0, b"efghi", true))
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::FrameTooLong { len: 5, max_len: 4 }))
+    ));
+    assert!(server.next().await.is_none());
+
+    let mut buf = Vec::new();
+    drop(server);
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x88\x15\x03\xf1max frame length: 4");
+}
+
+#[tokio::test]
+async fn test_max_fragments() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .limits(Limits::default().max_fragments(Some(2)))
+        .serve(one);
+
+    two.write_all(&encode_frame(1, b"a", false)).await.unwrap();
+    two.write_all(&encode_frame(0, b"b", true)).await.unwrap();
+    two.write_all(&encode_frame(1, b"a", false)).await.unwrap();
+    two.write_all(&encode_frame(9, b"", true)).await.unwrap();
+    two.write_all(&encode_frame(0, b"b", false)).await.unwrap();
+    two.write_all(&encode_frame(0, b"c", true)).await.unwrap();
+
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("ab"));
+    assert!(server.next().await.unwrap().unwrap().is_ping());
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::TooManyFragments { max_fragments: 2 }))
+    ));
+    assert!(server.next().await.is_none());
+
+    let mut buf = Vec::new();
+    drop(server);
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x8a\x00\x88\x12\x03\xf0max fragments: 2");
+}
+
+#[tokio::test]
+async fn test_max_text_and_binary_len() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .limits(
+            Limits::default()
+                .max_text_len(Some(4))
+                .max_binary_len(Some(8)),
+        )
+        .serve(one);
+
+    two.write_all(&encode_frame(2, b"abcdefgh", true))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(1, b"abc", false))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(0, b"de", true)).await.unwrap();
+
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(**msg.as_payload(), *b"abcdefgh");
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::PayloadTooLong { len: 5, max_len: 4 }))
+    ));
+    assert!(server.next().await.is_none());
+
+    let mut buf = Vec::new();
+    drop(server);
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x88\x0f\x03\xf1max length: 4");
+}
@@ -0,0 +1,47 @@
+use tokio_websockets::{
+    proto::{MessageKind, MessageRef},
+    CloseCode, CloseFrame, Message,
+};
+
+#[test]
+fn test_kind() {
+    match Message::text("hello").kind() {
+        MessageRef::Text(text) => assert_eq!(text, "hello"),
+        kind => panic!("unexpected kind: {kind:?}"),
+    }
+
+    match Message::ping("ping").kind() {
+        MessageRef::Ping(payload) => assert_eq!(&**payload, b"ping"),
+        kind => panic!("unexpected kind: {kind:?}"),
+    }
+
+    match Message::close(Some(CloseCode::GOING_AWAY), "bye").kind() {
+        MessageRef::Close(Some(frame)) => {
+            assert_eq!(frame.code(), CloseCode::GOING_AWAY);
+            assert_eq!(frame.reason(), "bye");
+        }
+        kind => panic!("unexpected kind: {kind:?}"),
+    }
+
+    assert!(matches!(
+        Message::close(None, "").kind(),
+        MessageRef::Close(None)
+    ));
+}
+
+#[test]
+fn test_into_kind() {
+    match Message::text("hello").into_kind() {
+        MessageKind::Text(text) => assert_eq!(text, "hello"),
+        kind => panic!("unexpected kind: {kind:?}"),
+    }
+
+    match Message::binary(vec![1, 2, 3]).into_kind() {
+        MessageKind::Binary(payload) => assert_eq!(&*payload, [1, 2, 3]),
+        kind => panic!("unexpected kind: {kind:?}"),
+    }
+
+    let frame = CloseFrame::new(CloseCode::POLICY_VIOLATION, "no").unwrap();
+    let message = Message::from(MessageKind::Close(Some(frame.clone())));
+    assert!(matches!(message.into_kind(), MessageKind::Close(Some(f)) if f == frame));
+}
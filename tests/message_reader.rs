@@ -0,0 +1,81 @@
+#![cfg(feature = "server")]
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{Error, Limits, ServerBuilder};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+#[tokio::test]
+async fn test_message_reader() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(&encode_frame(2, b"abc", false))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(9, b"", true)).await.unwrap();
+    two.write_all(&encode_frame(0, b"def", true)).await.unwrap();
+
+    let mut reader = server.next_message_reader().await.unwrap().unwrap();
+    assert!(reader.is_binary());
+
+    let mut payload = Vec::new();
+    reader.read_to_end(&mut payload).await.unwrap();
+    assert_eq!(payload, b"abcdef");
+}
+
+#[tokio::test]
+async fn test_message_reader_limits() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .limits(Limits::default().max_payload_len(Some(4)))
+        .serve(one);
+
+    two.write_all(&encode_frame(1, b"abc", false))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(0, b"def", true)).await.unwrap();
+
+    let mut reader = server.next_message_reader().await.unwrap().unwrap();
+    assert!(reader.is_text());
+
+    let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+    assert!(matches!(
+        err.into_inner().unwrap().downcast_ref::<Error>(),
+        Some(Error::PayloadTooLong { len: 6, max_len: 4 })
+    ));
+}
+
+#[tokio::test]
+async fn test_message_reader_drop_discards_message() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(&encode_frame(1, b"abc", false))
+        .await
+        .unwrap();
+    two.write_all(&encode_frame(0, b"def", true)).await.unwrap();
+    two.write_all(&encode_frame(1, b"next", true))
+        .await
+        .unwrap();
+
+    let mut reader = server.next_message_reader().await.unwrap().unwrap();
+    let mut buf = [0; 3];
+    reader.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"abc");
+    drop(reader);
+
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("next"));
+}
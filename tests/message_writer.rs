@@ -0,0 +1,121 @@
+#![cfg(feature = "server")]
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{
+    proto::{OpCode, ProtocolError},
+    Config, Error, Message, ServerBuilder,
+};
+
+#[tokio::test]
+async fn test_message_writer() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    let mut writer = server.send_streaming(OpCode::Binary);
+    writer.write_all(b"abc").await.unwrap();
+    writer.flush().await.unwrap();
+    writer.queue_control(Message::ping("ping")).unwrap();
+    writer.write_all(b"def").await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+
+    let mut buf = [0; 16];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x02\x03abc\x89\x04ping\x80\x03def");
+}
+
+#[tokio::test]
+async fn test_message_writer_frame_size() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .config(Config::default().frame_size(4))
+        .serve(one);
+
+    let mut writer = server.send_streaming(OpCode::Text);
+    writer.write_all(b"Hello!").await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+
+    let mut buf = [0; 10];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x01\x04Hell\x80\x02o!");
+}
+
+#[tokio::test]
+async fn test_message_writer_full_final_frame() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .config(Config::default().frame_size(4))
+        .serve(one);
+
+    // The last full frame is marked as final instead of sending an empty one
+    let mut writer = server.send_streaming(OpCode::Binary);
+    writer.write_all(b"abcdefgh").await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+    drop(server);
+
+    let mut buf = Vec::new();
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x02\x04abcd\x80\x04efgh");
+}
+
+#[tokio::test]
+async fn test_message_writer_drop_invalid_utf8() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    let mut writer = server.send_streaming(OpCode::Text);
+    writer.write_all(b"a\xc3").await.unwrap();
+    writer.flush().await.unwrap();
+    drop(writer);
+    server.flush().await.unwrap();
+    drop(server);
+
+    // The connection is closed instead of finishing the invalid message
+    let mut buf = Vec::new();
+    two.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(buf, b"\x01\x02a\xc3\x88\x02\x03\xef");
+}
+
+#[tokio::test]
+async fn test_message_writer_validates_utf8() {
+    let (one, _two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    // A valid character split across writes is accepted
+    let mut writer = server.send_streaming(OpCode::Text);
+    writer.write_all(&[0xc3]).await.unwrap();
+    writer.flush().await.unwrap();
+    writer.write_all(&[0xa4]).await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+
+    // An incomplete character at the end of the message is rejected
+    let mut writer = server.send_streaming(OpCode::Text);
+    writer.write_all(&[0xc3]).await.unwrap();
+    let err = writer.shutdown().await.unwrap_err();
+    assert!(matches!(
+        err.into_inner().unwrap().downcast_ref::<Error>(),
+        Some(Error::Protocol(ProtocolError::InvalidUtf8))
+    ));
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_message_writer_roundtrip() {
+    let (one, two) = duplex(usize::MAX);
+    let mut client = tokio_websockets::ClientBuilder::new()
+        .config(Config::default().frame_size(3))
+        .take_over(one);
+    let mut server = ServerBuilder::new().serve(two);
+
+    let mut writer = client.send_streaming(OpCode::Text);
+    writer.write_all("Hällö, wörld!".as_bytes()).await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("Hällö, wörld!"));
+}
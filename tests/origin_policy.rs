@@ -0,0 +1,104 @@
+#![cfg(feature = "server")]
+use tokio_websockets::{server::Matcher, upgrade, Error, ServerBuilder};
+
+mod common;
+
+/// A valid upgrade request without a `Host` header and the final empty line.
+const UPGRADE_REQUEST: &str = "GET / HTTP/1.1\r\nUpgrade: websocket\r\nConnection: \
+                               Upgrade\r\nSec-WebSocket-Key: \
+                               dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n";
+
+/// Sends an upgrade request with the given extra headers to the server and
+/// returns the result of the handshake and the response of the server.
+async fn handshake(server: &ServerBuilder, headers: &str) -> (Result<(), Error>, String) {
+    common::handshake(server, &format!("{UPGRADE_REQUEST}{headers}\r\n")).await
+}
+
+#[tokio::test]
+async fn test_allowed_origins() {
+    let server = ServerBuilder::new().allowed_origins([
+        Matcher::exact("https://example.com"),
+        Matcher::subdomains("https://*.example.com"),
+        Matcher::predicate(|origin| origin.ends_with(".test")),
+    ]);
+
+    for origin in [
+        "https://example.com",
+        "https://API.example.com",
+        "https://a.b.example.com",
+        "http://localhost.test",
+    ] {
+        let (result, response) = handshake(&server, &format!("Origin: {origin}\r\n")).await;
+        assert!(result.is_ok(), "{origin} was not allowed");
+        assert!(response.starts_with("HTTP/1.1 101"));
+    }
+
+    // Browsers always send an origin, other clients may not
+    assert!(handshake(&server, "").await.0.is_ok());
+
+    for origin in [
+        "https://evil.com",
+        "http://example.com",
+        "https://example.com.evil.com",
+        "https://evilexample.com",
+    ] {
+        let (result, response) = handshake(&server, &format!("Origin: {origin}\r\n")).await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::Upgrade(upgrade::Error::OriginNotAllowed))
+            ),
+            "{origin} was allowed"
+        );
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    }
+}
+
+#[tokio::test]
+async fn test_allowed_hosts() {
+    let server = ServerBuilder::new()
+        .allowed_hosts([Matcher::exact("example.com"), Matcher::exact("[::1]")]);
+
+    assert!(handshake(&server, "Host: example.com:8080\r\n")
+        .await
+        .0
+        .is_ok());
+    assert!(handshake(&server, "Host: [::1]:80\r\n").await.0.is_ok());
+
+    for headers in ["Host: evil.com\r\n", ""] {
+        let (result, response) = handshake(&server, headers).await;
+        assert!(matches!(
+            result,
+            Err(Error::Upgrade(upgrade::Error::HostNotAllowed))
+        ));
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    }
+}
+
+#[tokio::test]
+async fn test_multiple_values_rejected() {
+    let server = ServerBuilder::new()
+        .allowed_origins([Matcher::exact("https://example.com")])
+        .allowed_hosts([Matcher::exact("example.com")]);
+
+    let (result, response) = handshake(
+        &server,
+        "Host: example.com\r\nOrigin: https://example.com\r\nOrigin: https://evil.com\r\n",
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(Error::Upgrade(upgrade::Error::OriginNotAllowed))
+    ));
+    assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+
+    let (result, _) = handshake(
+        &server,
+        "Host: example.com\r\nHost: evil.com\r\nOrigin: https://example.com\r\n",
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(Error::Upgrade(upgrade::Error::HostNotAllowed))
+    ));
+}
@@ -0,0 +1,155 @@
+#![cfg(all(feature = "client", feature = "server", feature = "permessage-deflate"))]
+use futures_util::{SinkExt, StreamExt};
+use http::header::SEC_WEBSOCKET_EXTENSIONS;
+use tokio::io::{duplex, DuplexStream};
+use tokio_websockets::{
+    extensions::PerMessageDeflate, ClientBuilder, Config, Error, Limits, Message, ServerBuilder,
+    WebSocketStream,
+};
+
+async fn connect(
+    client: ClientBuilder<'static>,
+    server: ServerBuilder,
+) -> (
+    WebSocketStream<DuplexStream>,
+    WebSocketStream<DuplexStream>,
+    Option<String>,
+) {
+    let (one, two) = duplex(usize::MAX);
+    let client = client.uri("ws://localhost/").unwrap();
+
+    let (client, server) = tokio::join!(client.connect_on(one), server.accept(two));
+    let (client, response) = client.unwrap();
+    let (_, server) = server.unwrap();
+    let extensions = response
+        .headers()
+        .get(SEC_WEBSOCKET_EXTENSIONS)
+        .map(|value| value.to_str().unwrap().to_owned());
+
+    (client, server, extensions)
+}
+
+#[tokio::test]
+async fn test_permessage_deflate_roundtrip() {
+    let (mut client, mut server, extensions) = connect(
+        ClientBuilder::new()
+            .config(Config::default().frame_size(64))
+            .extension(PerMessageDeflate::new().client_max_window_bits(10)),
+        ServerBuilder::new()
+            .config(Config::default().frame_size(64))
+            .extension(PerMessageDeflate::new().server_no_context_takeover(true)),
+    )
+    .await;
+
+    assert_eq!(
+        extensions.as_deref(),
+        Some("permessage-deflate; server_no_context_takeover; client_max_window_bits=10")
+    );
+
+    let text = "Hello, world! ".repeat(100);
+    let binary: Vec<u8> = (0..=255).cycle().take(4096).collect();
+
+    for _ in 0..3 {
+        client.send(Message::ping("ping")).await.unwrap();
+        client.send(Message::text(text.clone())).await.unwrap();
+        client.send(Message::binary(binary.clone())).await.unwrap();
+
+        let msg = server.next().await.unwrap().unwrap();
+        assert!(msg.is_ping());
+
+        let msg = server.next().await.unwrap().unwrap();
+        assert_eq!(msg.as_text(), Some(text.as_str()));
+        server.send(msg).await.unwrap();
+
+        let msg = server.next().await.unwrap().unwrap();
+        assert_eq!(&**msg.as_payload(), &binary[..]);
+        server.send(msg).await.unwrap();
+
+        let msg = client.next().await.unwrap().unwrap();
+        assert!(msg.is_pong());
+
+        let msg = client.next().await.unwrap().unwrap();
+        assert_eq!(msg.as_text(), Some(text.as_str()));
+
+        let msg = client.next().await.unwrap().unwrap();
+        assert_eq!(&**msg.as_payload(), &binary[..]);
+    }
+}
+
+#[tokio::test]
+async fn test_permessage_deflate_empty_message() {
+    let (mut client, mut server, _) = connect(
+        ClientBuilder::new().permessage_deflate(PerMessageDeflate::new()),
+        ServerBuilder::new().permessage_deflate(PerMessageDeflate::new()),
+    )
+    .await;
+
+    for message in [
+        Message::text("hello"),
+        Message::text(""),
+        Message::binary(""),
+        Message::text("world"),
+    ] {
+        client.send(message.clone()).await.unwrap();
+        let msg = server.next().await.unwrap().unwrap();
+        assert_eq!(msg.is_text(), message.is_text());
+        assert_eq!(&**msg.as_payload(), &**message.as_payload());
+
+        server.send(msg).await.unwrap();
+        let msg = client.next().await.unwrap().unwrap();
+        assert_eq!(msg.is_text(), message.is_text());
+        assert_eq!(&**msg.as_payload(), &**message.as_payload());
+    }
+}
+
+#[tokio::test]
+async fn test_permessage_deflate_not_offered() {
+    let (mut client, mut server, extensions) = connect(
+        ClientBuilder::new(),
+        ServerBuilder::new().extension(PerMessageDeflate::new()),
+    )
+    .await;
+
+    assert_eq!(extensions, None);
+
+    client.send(Message::text("uncompressed")).await.unwrap();
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("uncompressed"));
+}
+
+#[tokio::test]
+async fn test_permessage_deflate_not_accepted() {
+    let (mut client, mut server, extensions) = connect(
+        ClientBuilder::new().extension(PerMessageDeflate::new()),
+        ServerBuilder::new(),
+    )
+    .await;
+
+    assert_eq!(extensions, None);
+
+    server.send(Message::text("uncompressed")).await.unwrap();
+    let msg = client.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("uncompressed"));
+}
+
+#[tokio::test]
+async fn test_permessage_deflate_decompressed_size_limited() {
+    let (mut client, mut server, _) = connect(
+        ClientBuilder::new().extension(PerMessageDeflate::new()),
+        ServerBuilder::new()
+            .limits(Limits::default().max_payload_len(Some(1024)))
+            .extension(PerMessageDeflate::new()),
+    )
+    .await;
+
+    // Compresses to far less than the limit
+    client
+        .send(Message::binary(vec![0; 1024 * 1024]))
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::PayloadTooLong { max_len: 1024, .. }))
+    ));
+}
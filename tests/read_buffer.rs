@@ -0,0 +1,63 @@
+#![cfg(feature = "server")]
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::StreamExt;
+use tokio::{
+    io::{duplex, AsyncWriteExt},
+    time::timeout,
+};
+use tokio_websockets::{Config, ServerBuilder};
+
+const LARGE_LEN: usize = 1024 * 1024;
+
+fn encode_large_binary_frame() -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8(0x82);
+    dst.put_u8(127 + 128);
+    dst.put_u64(LARGE_LEN as u64);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.put_bytes(0, LARGE_LEN);
+
+    dst.freeze()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_read_buffer_shrinks_when_idle() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .config(
+            Config::default()
+                .read_buffer_capacity(1024)
+                .read_buffer_high_water_mark(Some(64 * 1024)),
+        )
+        .serve(one);
+
+    two.write_all(&encode_large_binary_frame()).await.unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.into_payload().len(), LARGE_LEN);
+
+    // Waiting for the next message shrinks the buffer
+    assert!(timeout(Duration::from_secs(1), server.next())
+        .await
+        .is_err());
+    assert!(server.buffer_footprint() < 64 * 1024);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_read_buffer_keeps_capacity_without_high_water_mark() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(&encode_large_binary_frame()).await.unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.into_payload().len(), LARGE_LEN);
+
+    assert!(timeout(Duration::from_secs(1), server.next())
+        .await
+        .is_err());
+    assert!(server.buffer_footprint() >= LARGE_LEN);
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "server")]
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use tokio_websockets::{upgrade, Error, ServerBuilder};
+
+use self::common::handshake;
+
+mod common;
+
+const UNSUPPORTED_VERSION: &str = "GET / HTTP/1.1\r\nUpgrade: websocket\r\nConnection: \
+                                   Upgrade\r\nSec-WebSocket-Key: \
+                                   dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n\r\n";
+
+#[tokio::test]
+async fn test_unsupported_version() {
+    let (result, response) = handshake(&ServerBuilder::new(), UNSUPPORTED_VERSION).await;
+
+    assert!(matches!(
+        result,
+        Err(Error::Upgrade(upgrade::Error::UnsupportedWebSocketVersion))
+    ));
+    assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+    assert!(response.contains("sec-websocket-version: 13\r\n"));
+    assert!(response.contains("upgrade: websocket\r\n"));
+    assert!(response.ends_with("content-length: 0\r\n\r\n"));
+}
+
+#[tokio::test]
+async fn test_not_an_upgrade() {
+    let (result, response) = handshake(
+        &ServerBuilder::new(),
+        "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::Upgrade(upgrade::Error::MissingHeader("Upgrade")))
+    ));
+    assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+}
+
+#[tokio::test]
+async fn test_missing_key() {
+    let (_, response) = handshake(
+        &ServerBuilder::new().rejection_details(true),
+        "GET / HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Version: \
+         13\r\n\r\n",
+    )
+    .await;
+
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.contains("content-type: text/plain; charset=utf-8\r\n"));
+    assert!(response.ends_with("\r\n\r\nmissing required header: Sec-WebSocket-Key"));
+}
+
+#[tokio::test]
+async fn test_custom_rejection_response() {
+    let server = ServerBuilder::new().rejection_response(|error| match error {
+        upgrade::Error::UnsupportedWebSocketVersion => {
+            let mut response = Response::new(Bytes::from_static(b"please upgrade"));
+            *response.status_mut() = StatusCode::UPGRADE_REQUIRED;
+            response
+                .headers_mut()
+                .insert("sec-websocket-version", "13".parse().unwrap());
+            Some(response)
+        }
+        _ => None,
+    });
+
+    let (_, response) = handshake(&server, UNSUPPORTED_VERSION).await;
+    assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+    assert!(response.ends_with("\r\n\r\nplease upgrade"));
+
+    // Other errors use the default response
+    let (_, response) = handshake(&server, "GET / HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+    assert!(response.ends_with("content-length: 0\r\n\r\n"));
+}
+
+#[tokio::test]
+async fn test_custom_rejection_response_status() {
+    let server = ServerBuilder::new().rejection_response(|_| Some(Response::new(Bytes::new())));
+
+    let (_, response) = handshake(&server, UNSUPPORTED_VERSION).await;
+    assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use tokio::net::TcpStream;
+use tokio_websockets::{EncodedMessage, Message, Payload, Utf8Payload, WebSocketStream};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_send_sync() {
+    assert_send_sync::<Payload>();
+    assert_send_sync::<Utf8Payload>();
+    assert_send_sync::<Message>();
+    assert_send_sync::<EncodedMessage>();
+    assert_send_sync::<WebSocketStream<TcpStream>>();
+}
+
+#[tokio::test]
+async fn test_shared_message() {
+    let message = Arc::new(Message::text(String::from("hello")));
+
+    let tasks: Vec<_> = (0..4)
+        .map(|_| {
+            let message = Arc::clone(&message);
+            tokio::spawn(async move { message.as_text().map(str::len) })
+        })
+        .collect();
+
+    for task in tasks {
+        assert_eq!(task.await.unwrap(), Some(5));
+    }
+}
+
+#[test]
+fn test_payload_zero_copy() {
+    let buf = BytesMut::from(&b"hello"[..]);
+    let ptr = buf.as_ptr();
+
+    let payload = Payload::from(buf);
+    let clone = payload.clone();
+    assert_eq!(clone.as_ptr(), ptr);
+    drop(clone);
+
+    // The payload is unique again once all clones were dropped
+    let buf = BytesMut::from(payload);
+    assert_eq!(buf.as_ptr(), ptr);
+
+    let bytes = Bytes::from(Payload::from(buf));
+    assert_eq!(bytes.as_ptr(), ptr);
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "server")]
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{FutureExt, SinkExt, StreamExt};
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{
+    proto::{OpCode, ProtocolError},
+    Config, Error, Message, ServerBuilder,
+};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+#[tokio::test]
+async fn test_split_halves_are_send() {
+    let (one, mut two) = duplex(usize::MAX);
+    let (mut read, mut write) = ServerBuilder::new().serve(one).split();
+
+    two.write_all(&encode_frame(1, b"Hello", true))
+        .await
+        .unwrap();
+
+    let reader = tokio::spawn(async move { read.next().await.unwrap().unwrap() });
+    let writer = tokio::spawn(async move { write.send(Message::text("world")).await });
+
+    let msg = reader.await.unwrap();
+    assert_eq!(msg.as_text(), Some("Hello"));
+    writer.await.unwrap().unwrap();
+
+    let mut buf = [0; 7];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x81\x05world");
+}
+
+#[tokio::test]
+async fn test_split_read_half_replies() {
+    let (one, mut two) = duplex(usize::MAX);
+    let (mut read, mut write) = ServerBuilder::new().serve(one).split();
+
+    two.write_all(&encode_frame(9, b"ping", true))
+        .await
+        .unwrap();
+    assert!(read.next().await.unwrap().unwrap().is_ping());
+
+    // The read half flushes the pong before it waits for more data
+    assert!(read.next().now_or_never().is_none());
+    let mut buf = [0; 6];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x8a\x04ping");
+
+    two.write_all(&encode_frame(8, b"\x03\xe8", true))
+        .await
+        .unwrap();
+    assert!(read.next().await.unwrap().unwrap().is_close());
+    assert!(read.next().await.is_none());
+
+    let mut buf = [0; 4];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x88\x02\x03\xe8");
+
+    assert!(matches!(
+        write.send(Message::text("too late")).await,
+        Err(Error::AlreadyClosed)
+    ));
+}
+
+#[tokio::test]
+async fn test_split_keeps_buffered_frames() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    for payload in [&b"one"[..], b"two", b"three"] {
+        two.write_all(&encode_frame(2, payload, true))
+            .await
+            .unwrap();
+    }
+
+    // Reads all frames into the buffer, but only returns the first one
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(&**msg.as_payload(), b"one");
+
+    let (mut read, write) = server.split();
+    let msg = read.next().await.unwrap().unwrap();
+    assert_eq!(&**msg.as_payload(), b"two");
+
+    let mut server = read.reunite(write).unwrap();
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(&**msg.as_payload(), b"three");
+}
+
+#[tokio::test]
+async fn test_reunite_mismatched_halves() {
+    let (one, _) = duplex(usize::MAX);
+    let (two, _) = duplex(usize::MAX);
+    let (read, _write) = ServerBuilder::new().serve(one).split();
+    let (_read, write) = ServerBuilder::new().serve(two).split();
+
+    assert!(read.reunite(write).is_err());
+}
+
+#[tokio::test]
+async fn test_split_streaming_answers_pings() {
+    let (one, mut two) = duplex(usize::MAX);
+    let (mut read, mut write) = ServerBuilder::new().serve(one).split();
+
+    let mut writer = write.send_streaming(OpCode::Binary);
+    writer.write_all(b"abc").await.unwrap();
+    writer.flush().await.unwrap();
+
+    // The read half answers pings while the message is written
+    two.write_all(&encode_frame(9, b"ping", true))
+        .await
+        .unwrap();
+    assert!(read.next().await.unwrap().unwrap().is_ping());
+    assert!(read.next().now_or_never().is_none());
+
+    writer.write_all(b"def").await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+
+    let mut buf = [0; 16];
+    two.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, *b"\x02\x03abc\x8a\x04ping\x80\x03def");
+}
+
+#[tokio::test]
+async fn test_split_no_messages_while_streaming() {
+    let (one, _two) = duplex(usize::MAX);
+    let (_read, mut write) = ServerBuilder::new().serve(one).split();
+
+    let mut writer = write.send_streaming(OpCode::Text);
+    writer.write_all(b"abc").await.unwrap();
+    writer.flush().await.unwrap();
+    // Leaking the writer leaves its message in progress
+    std::mem::forget(writer);
+
+    assert!(matches!(
+        write.send(Message::text("x")).await,
+        Err(Error::Protocol(ProtocolError::InvalidOpcode))
+    ));
+    let encoded = Message::binary("x").prepare(&Config::default());
+    assert!(matches!(
+        write.send_encoded(&encoded).await,
+        Err(Error::Protocol(ProtocolError::InvalidOpcode))
+    ));
+
+    // Control messages can still be sent in between
+    write.send(Message::ping("ping")).await.unwrap();
+}
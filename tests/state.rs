@@ -0,0 +1,59 @@
+#![cfg(feature = "server")]
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{duplex, AsyncWriteExt};
+use tokio_websockets::{proto::StreamState, Limits, Message, ServerBuilder};
+
+fn encode_frame(opcode: u8, payload: &[u8], is_final: bool) -> Bytes {
+    let mut dst = BytesMut::new();
+
+    dst.put_u8((u8::from(is_final) << 7) + opcode);
+    dst.put_u8(payload.len() as u8 + 128);
+    dst.extend_from_slice(&[0, 0, 0, 0]);
+    dst.extend_from_slice(payload);
+
+    dst.freeze()
+}
+
+#[tokio::test]
+async fn test_state() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    assert_eq!(server.state(), StreamState::Active);
+    assert!(!server.is_closed());
+
+    server.feed(Message::binary("test")).await.unwrap();
+    assert_eq!(server.buffered_amount(), 6);
+    server.flush().await.unwrap();
+    assert_eq!(server.buffered_amount(), 0);
+
+    two.write_all(&encode_frame(8, b"\x03\xe8", true))
+        .await
+        .unwrap();
+
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert_eq!(server.state(), StreamState::ClosedByPeer);
+    assert!(!server.is_closed());
+
+    assert!(server.next().await.is_none());
+    assert_eq!(server.state(), StreamState::CloseAcknowledged);
+    assert!(server.is_closed());
+}
+
+#[tokio::test]
+async fn test_set_limits() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .limits(Limits::default().max_payload_len(Some(4)))
+        .serve(one);
+
+    server.set_limits(Limits::default().max_payload_len(Some(8)));
+
+    two.write_all(&encode_frame(2, b"abcdefgh", true))
+        .await
+        .unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(&message.as_payload()[..], b"abcdefgh");
+}
@@ -0,0 +1,108 @@
+#![cfg(all(feature = "client", feature = "server"))]
+use http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{upgrade, ClientBuilder, Error, ServerBuilder};
+
+#[tokio::test]
+async fn test_subprotocol_negotiated() {
+    let (one, two) = duplex(usize::MAX);
+    let client = ClientBuilder::new()
+        .uri("ws://localhost/")
+        .unwrap()
+        .subprotocols(["v1.json", "graphql-transport-ws"]);
+    let server = ServerBuilder::new().subprotocols(["graphql-transport-ws", "v2.json", "v1.json"]);
+
+    let (client, server) = tokio::join!(client.connect_on(one), server.accept(two));
+    let (client, response) = client.unwrap();
+    let (request, server) = server.unwrap();
+
+    assert_eq!(
+        request.headers()[SEC_WEBSOCKET_PROTOCOL],
+        "v1.json, graphql-transport-ws"
+    );
+    assert_eq!(
+        response.headers()[SEC_WEBSOCKET_PROTOCOL],
+        "graphql-transport-ws"
+    );
+    assert_eq!(client.subprotocol(), Some("graphql-transport-ws"));
+    assert_eq!(server.subprotocol(), Some("graphql-transport-ws"));
+}
+
+#[tokio::test]
+async fn test_subprotocol_not_supported() {
+    let (one, two) = duplex(usize::MAX);
+    let client = ClientBuilder::new()
+        .uri("ws://localhost/")
+        .unwrap()
+        .subprotocols(["v1.json"]);
+    let server = ServerBuilder::new().subprotocols(["v2.json"]);
+
+    let (client, server) = tokio::join!(client.connect_on(one), server.accept(two));
+    let (client, response) = client.unwrap();
+    let (_, server) = server.unwrap();
+
+    assert!(!response.headers().contains_key(SEC_WEBSOCKET_PROTOCOL));
+    assert_eq!(client.subprotocol(), None);
+    assert_eq!(server.subprotocol(), None);
+}
+
+#[tokio::test]
+async fn test_subprotocol_not_offered() {
+    let (one, mut two) = duplex(usize::MAX);
+    let client = ClientBuilder::new()
+        .uri("ws://localhost/")
+        .unwrap()
+        .subprotocols(["v1.json"]);
+
+    let server = async move {
+        let mut buf = [0; 1024];
+        let n = two.read(&mut buf).await.unwrap();
+        let request = std::str::from_utf8(&buf[..n]).unwrap();
+        let key = request
+            .lines()
+            .find_map(|line| line.strip_prefix("Sec-WebSocket-Key: "))
+            .unwrap();
+        let accept = accept_value(key).await;
+
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: \
+             Upgrade\r\nSec-WebSocket-Accept: {accept}\r\nSec-WebSocket-Protocol: v2.json\r\n\r\n"
+        );
+        two.write_all(response.as_bytes()).await.unwrap();
+        two
+    };
+
+    let (client, _two) = tokio::join!(client.connect_on(one), server);
+    assert!(matches!(
+        client,
+        Err(Error::Upgrade(upgrade::Error::InvalidSubprotocol))
+    ));
+}
+
+#[test]
+#[should_panic = "subprotocols must be valid HTTP tokens"]
+fn test_invalid_subprotocol() {
+    let _ = ServerBuilder::new().subprotocols(["v1.json, v2.json"]);
+}
+
+/// Computes the `Sec-WebSocket-Accept` value for a key by letting a server
+/// answer a handshake with it.
+async fn accept_value(key: &str) -> String {
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: \
+         Upgrade\r\nSec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    );
+
+    let (mut one, two) = duplex(usize::MAX);
+    one.write_all(request.as_bytes()).await.unwrap();
+    let _server = ServerBuilder::new().accept(two).await.unwrap();
+
+    let mut buf = [0; 1024];
+    let n = one.read(&mut buf).await.unwrap();
+    std::str::from_utf8(&buf[..n])
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("Sec-WebSocket-Accept: "))
+        .unwrap()
+        .to_owned()
+}
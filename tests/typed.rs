@@ -0,0 +1,126 @@
+#![cfg(all(feature = "client", feature = "server"))]
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::duplex;
+use tokio_websockets::{
+    codec::{Error, MessageCodec, Typed},
+    ClientBuilder, CloseCode, Message, ServerBuilder,
+};
+
+/// Codec that sends numbers as decimal text.
+struct Decimal;
+
+impl MessageCodec<u32> for Decimal {
+    type DecodeError = std::num::ParseIntError;
+    type EncodeError = std::convert::Infallible;
+
+    fn encode(&mut self, item: &u32) -> Result<Message, Self::EncodeError> {
+        Ok(Message::text(item.to_string()))
+    }
+
+    fn decode(&mut self, message: Message) -> Result<u32, Self::DecodeError> {
+        message.as_text().unwrap_or_default().parse()
+    }
+}
+
+#[tokio::test]
+async fn test_typed_roundtrip() {
+    let (one, two) = duplex(usize::MAX);
+    let mut server = Typed::new(ServerBuilder::new().serve(one), Decimal);
+    let mut client = Typed::new(ClientBuilder::new().take_over(two), Decimal);
+
+    client.send(42).await.unwrap();
+    client.get_mut().send(Message::ping("")).await.unwrap();
+    client.send(7).await.unwrap();
+
+    assert_eq!(server.next().await.unwrap().unwrap(), 42);
+    assert_eq!(server.next().await.unwrap().unwrap(), 7);
+}
+
+#[tokio::test]
+async fn test_typed_decode_error_closes() {
+    let (one, two) = duplex(usize::MAX);
+    let mut server = Typed::new(ServerBuilder::new().serve(one), Decimal)
+        .close_on_decode_error(Some(CloseCode::UNSUPPORTED_DATA));
+    let mut client = ClientBuilder::new().take_over(two);
+
+    client.send(Message::text("nope")).await.unwrap();
+
+    assert!(matches!(server.next().await, Some(Err(Error::Codec(_)))));
+    server.flush().await.unwrap();
+
+    let close = client.next().await.unwrap().unwrap();
+    assert_eq!(close.as_close().unwrap().0, CloseCode::UNSUPPORTED_DATA);
+}
+
+#[tokio::test]
+async fn test_typed_decode_error_without_close() {
+    let (one, two) = duplex(usize::MAX);
+    let mut server =
+        Typed::new(ServerBuilder::new().serve(one), Decimal).close_on_decode_error(None);
+    let mut client = ClientBuilder::new().take_over(two);
+
+    client.send(Message::text("nope")).await.unwrap();
+    client.send(Message::text("1")).await.unwrap();
+
+    assert!(matches!(server.next().await, Some(Err(Error::Codec(_)))));
+    assert_eq!(server.next().await.unwrap().unwrap(), 1);
+}
+
+#[cfg(feature = "serde_json")]
+#[tokio::test]
+async fn test_json() {
+    use serde::{Deserialize, Serialize};
+    use tokio_websockets::codec::Json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    let (one, two) = duplex(usize::MAX);
+    let mut server = Typed::new(ServerBuilder::new().serve(one), Json);
+    let mut client = ClientBuilder::new().take_over(two);
+
+    client
+        .send(Message::text(r#"{"x":1,"y":2}"#))
+        .await
+        .unwrap();
+    assert_eq!(server.next().await.unwrap().unwrap(), Point { x: 1, y: 2 });
+
+    server.send(Point { x: 3, y: 4 }).await.unwrap();
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some(r#"{"x":3,"y":4}"#));
+}
+
+#[cfg(all(feature = "ciborium", feature = "rmp-serde"))]
+#[test]
+fn test_binary_codecs() {
+    use tokio_websockets::codec::{Cbor, MessagePack};
+
+    let items = vec![String::from("a"), String::from("b")];
+
+    let message = MessageCodec::<Vec<String>>::encode(&mut Cbor, &items).unwrap();
+    assert!(message.is_binary());
+    let decoded: Vec<String> = Cbor.decode(message).unwrap();
+    assert_eq!(decoded, items);
+
+    let message = MessageCodec::<Vec<String>>::encode(&mut MessagePack, &items).unwrap();
+    assert!(message.is_binary());
+    let decoded: Vec<String> = MessagePack.decode(message).unwrap();
+    assert_eq!(decoded, items);
+}
+
+#[cfg(feature = "prost")]
+#[test]
+fn test_protobuf() {
+    use tokio_websockets::codec::Protobuf;
+
+    let message = Protobuf.encode(&String::from("hello")).unwrap();
+    assert!(message.is_binary());
+    let decoded: String = Protobuf.decode(message).unwrap();
+    assert_eq!(decoded, "hello");
+
+    let invalid: Result<String, _> = Protobuf.decode(Message::binary(vec![0xff]));
+    assert!(invalid.is_err());
+}
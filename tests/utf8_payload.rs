@@ -0,0 +1,53 @@
+use bytes::Bytes;
+use tokio_websockets::{proto::ProtocolError, Message, Payload, Utf8Payload};
+
+#[test]
+fn test_into_text() {
+    let text = Message::text("hello world").into_text().unwrap();
+    assert_eq!(text, "hello world");
+    assert_eq!(text.slice(6..), "world");
+
+    assert!(Message::binary("hello").into_text().is_err());
+    assert!(Message::text(vec![0xff]).into_text().is_err());
+}
+
+#[test]
+fn test_text_checked() {
+    let message = Message::text_checked(Bytes::from_static(b"hello")).unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+
+    assert!(matches!(
+        Message::text_checked(vec![0xff]),
+        Err(ProtocolError::InvalidUtf8)
+    ));
+}
+
+#[test]
+fn test_utf8_payload_conversions() {
+    let text = Utf8Payload::try_from(Bytes::from_static("grüße".as_bytes())).unwrap();
+    assert_eq!(&*text, "grüße");
+    assert_eq!(Message::text(text.clone()).as_text(), Some("grüße"));
+    assert_eq!(Bytes::from(text), "grüße".as_bytes());
+
+    assert!(Utf8Payload::try_from(vec![b'a', 0xc3]).is_err());
+}
+
+#[test]
+#[should_panic = "character boundaries"]
+fn test_utf8_payload_slice_within_char() {
+    let text = Utf8Payload::from("grüße");
+    _ = text.slice(..3);
+}
+
+#[test]
+fn test_payload_split() {
+    let mut payload = Payload::from(b"hello world".to_vec());
+    let hello = payload.split_to(5);
+    assert_eq!(&*hello, b"hello");
+    assert_eq!(&*payload, b" world");
+
+    let world = payload.split_off(1);
+    assert_eq!(&*payload, b" ");
+    assert_eq!(&*world, b"world");
+    assert_eq!(&*world.slice(1..3), b"or");
+}
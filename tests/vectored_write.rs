@@ -0,0 +1,155 @@
+#![cfg(feature = "server")]
+use std::{
+    io::{self, IoSlice},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::SinkExt;
+#[cfg(feature = "client")]
+use futures_util::StreamExt;
+#[cfg(feature = "client")]
+use tokio::io::{duplex, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+#[cfg(feature = "client")]
+use tokio_websockets::{ClientBuilder, Config};
+use tokio_websockets::{Message, ServerBuilder};
+
+/// I/O that records everything written to it and accepts at most `max_write`
+/// bytes per call.
+struct Recorder {
+    vectored: bool,
+    max_write: usize,
+    writes: usize,
+    written: Vec<u8>,
+}
+
+impl Recorder {
+    fn new(vectored: bool, max_write: usize) -> Self {
+        Self {
+            vectored,
+            max_write,
+            writes: 0,
+            written: Vec::new(),
+        }
+    }
+}
+
+impl AsyncRead for Recorder {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for Recorder {
+    fn is_write_vectored(&self) -> bool {
+        self.vectored
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        self.writes += 1;
+        let mut remaining = self.max_write;
+
+        for buf in bufs {
+            let len = buf.len().min(remaining);
+            self.written.extend_from_slice(&buf[..len]);
+            remaining -= len;
+        }
+
+        Poll::Ready(Ok(self.max_write - remaining))
+    }
+}
+
+fn expected() -> Vec<u8> {
+    let mut expected = Vec::new();
+
+    for i in 0..100u8 {
+        expected.extend_from_slice(&[0x82, 2, i, i]);
+    }
+
+    expected
+}
+
+async fn send_all(io: Recorder) -> (usize, Vec<u8>) {
+    let mut server = ServerBuilder::new().serve(io);
+
+    for i in 0..100u8 {
+        server.feed(Message::binary(vec![i, i])).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    let io = server.get_mut();
+
+    (io.writes, std::mem::take(&mut io.written))
+}
+
+#[tokio::test]
+async fn test_frames_are_written_at_once() {
+    let (writes, written) = send_all(Recorder::new(true, usize::MAX)).await;
+
+    assert_eq!(writes, 1);
+    assert_eq!(written, expected());
+}
+
+#[tokio::test]
+async fn test_partial_vectored_writes() {
+    let (writes, written) = send_all(Recorder::new(true, 7)).await;
+
+    assert_eq!(writes, 400 / 7 + 1);
+    assert_eq!(written, expected());
+}
+
+#[tokio::test]
+async fn test_non_vectored_writes() {
+    let (_, written) = send_all(Recorder::new(false, 3)).await;
+
+    assert_eq!(written, expected());
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_partially_masked_payload() {
+    // Larger than the buffer payloads are masked in, followed by another frame
+    let large: Vec<u8> = (0..20_000).map(|i| i as u8).collect();
+
+    let mut client = ClientBuilder::new()
+        .config(Config::default().flush_threshold(usize::MAX))
+        .take_over(Recorder::new(true, usize::MAX));
+    client.feed(Message::binary(large.clone())).await.unwrap();
+    client.feed(Message::binary("after")).await.unwrap();
+    client.flush().await.unwrap();
+    let written = std::mem::take(&mut client.get_mut().written);
+
+    let (one, mut two) = duplex(usize::MAX);
+    two.write_all(&written).await.unwrap();
+    let mut server = ServerBuilder::new().serve(one);
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(&*message.into_payload(), &large[..]);
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(&*message.into_payload(), b"after");
+}